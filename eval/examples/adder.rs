@@ -2,7 +2,8 @@
 //! 
 //! This test takes a randomized input string, tokenizes it, and evaluates
 //! the tokens to produce a list of numbers. The calculation is a prefix 
-//! addition of numbers.
+//! addition of numbers. Tokens are described by a `Dfa`, and produced
+//! by `iodyn::lex`, which reuses the tokens of unchanged input.
 //! 
 //! The test is preformed with an incremental tree and sequence combination,
 //! or with a vector(array) to test non-incremental performance.
@@ -25,6 +26,7 @@ use adapton::engine::manage::*;
 use adapton_lab::labviz::*;
 #[allow(unused)] use iodyn::IRaz;
 #[allow(unused)] use iodyn::archive_stack::{AtTail,AStack as IAStack};
+use iodyn::lex::{Dfa,START};
 #[allow(unused)] use eval::eval_iraz::EvalIRaz;
 #[allow(unused)] use eval::eval_vec::EvalVec;
 #[allow(unused)] use eval::eval_iastack::EvalIAStack;
@@ -48,6 +50,9 @@ impl Rand for Lang{
   	})
   }
 }
+impl From<Lang> for char {
+  fn from(l: Lang) -> char { l.0 }
+}
 
 /// Intermediate Lang
 #[derive(Debug,Clone,Copy,Hash,Eq,PartialEq)]
@@ -56,29 +61,35 @@ enum Token {
 	OpPlus,
 }
 
+/// Tokens of the input language
+///
+/// Numbers are split after 6 digits so that they don't overflow.
+/// Commas separate numbers but make no tokens.
+fn adder_dfa() -> Dfa<Token> {
+	let mut dfa = Dfa::new();
+	let mut digits = START;
+	for _ in 0..6 {
+		let more = dfa.add_state();
+		dfa.edge(digits, more, |c| c.is_digit(10));
+		dfa.accept(more, |s| Token::Num(s.parse().unwrap()));
+		digits = more;
+	}
+	let plus = dfa.add_state();
+	dfa.edge(START, plus, |c| c == '+');
+	dfa.accept(plus, |_| Token::OpPlus);
+	let comma = dfa.add_state();
+	dfa.edge(START, comma, |c| c == ',');
+	dfa.skip(comma);
+	dfa
+}
+
 /// Reference parser and tokenizer for later work
 #[allow(unused)]
 mod reference {
-	use super::{Token};
+	use super::{Token,adder_dfa};
 
 	fn tokenize(p: String) -> Vec<Token> {
-		p.chars().fold((Vec::new(),None),|(mut ts,part),c|{
-			if let Some(num) = c.to_digit(10) {
-  			// this multiplication might overflow!
-				let bigger = part.map_or(num,|p|p*10+num);
-				(ts,Some(bigger))
-			} else {
-				if let Some(num) = part {
-					ts.push(Token::Num(num));
-				}
-				match c {
-					'+' => ts.push(Token::OpPlus),
-					',' => {},
-					c => panic!("invalid char '{}'",c),
-				}
-				(ts,None)
-			}
-		}).0
+		adder_dfa().tokenize(&p)
 	}
 
 	fn parse(toks: Vec<Token>) -> Vec<u32> {
@@ -157,38 +168,6 @@ fn main2() {
   let do_trace = args.is_present("trace");
 	let coord = StdRng::from_seed(&[dataseed]);
 
-	// fold function that builds a sequece of tokens
-	fn tokenize_step<A: IFaceSeq<Token>>((ts,part):(A,Option<u32>),l:&Lang) -> (A,Option<u32>) {
-		let Lang(ref c) = *l;
-		if let Some(num) = c.to_digit(10) {
-			let bigger = part.map_or(num,|p|p*10+num);
-			// we need to split up big nums, so use max 6 digits
-			let (ts,bigger) = if bigger > 99999 {
-				(ts.seq_push(Token::Num(bigger)),num)
-			} else {(ts,bigger)};
-			(ts,Some(bigger))
-		} else {
-			let ts = if let Some(num) = part {
-				ts.seq_push(Token::Num(num))
-			} else { ts };
-			let ts = match *c {
-				'+' => {ts.seq_push(Token::OpPlus)},
-				',' => {ts},
-				c => panic!("invalid char '{}'",c),
-			};
-			(ts,None)
-		}
-	}
-	// auxiliary fold function that inserts incremental names
-	fn tokenize_meta<A: IFaceArchive<(u32,Option<Name>)>>((ts,part):(A,Option<u32>),(l,n):(u32,Option<Name>)) -> (A,Option<u32>) {
-		(ts.archive((l,n)),part)
-	}
-	// auxiliary fold function that completes the token sequence
-	fn tokenize_final<A: IFaceSeq<Token>>((ts,part):(A,Option<u32>)) -> A {
-		if let Some(num) = part {
-			ts.seq_push(Token::Num(num))
-		} else { ts }
-	}
 	// fold function that evaluates tokens to build a sequence of numbers
 	fn parse_step<A: IFaceSeq<u32>>(n: A, t: &Token) -> A {
 		match *t {
@@ -210,10 +189,9 @@ fn main2() {
 	fn parse_meta<A: IFaceArchive<(u32,Option<Name>)>>(num: A, (l,n): (u32,Option<Name>)) -> A {
 		num.archive((l,n))
 	}
-	let name_to_tree = name_of_string(String::from("to_tree"));
 	
 	// Test parameters - two nearly identical sets, differing
-	// by the type of accumulator used between the tokenize
+	// by the type of sequence used between the tokenize
 	// and parse stages
   let mut test_inc = EditComputeSequence{
     init: IncrementalInit {
@@ -223,27 +201,14 @@ fn main2() {
       coord: coord.clone(),
     },
     edit: BatchInsert(edits),
-    // The type here determines the type of data between the
-    // two computations (output from 1 = input to 2).
-    // The specific accumulator is determined below in the
-    // conversion to a test harness.
-    // TODO: Move this parameter elsewhere
+    // The type here is the type of data between the
+    // two computations (output from 1 = input to 2),
+    // the tokens made by the lexer from the input.
     comp: Compute2::<_,_,_,EvalIRaz<Token,StdRng>,_>::new(
-    	MFolder::new(
+    	Lexer::new(
     		name_of_string(String::from("tokenize")),
-				(IFaceNew::new(),None),
-				tokenize_step,
-				tokenize_meta,
-				|a|{
-					let ts = tokenize_final(a);
-					ns(name_to_tree.clone(),||{IncrementalFrom{
-						data: AtTail(ts), // This determines the accumulator type
-			      datagauge: datagauge,
-			      namegauge: namegauge,
-			      coord: coord.clone(),
-					}.create(&mut StdRng::new().unwrap()).1})
-				},
-			),
+    		adder_dfa(),
+    	),
     	MFolder::new(
     		name_of_string(String::from("parse")),
     		IFaceNew::new(),
@@ -262,27 +227,14 @@ fn main2() {
       coord: coord.clone(),
     },
     edit: BatchInsert(edits),
-    // The type here determines the type of data between the
-    // two computations (output from 1 = input to 2).
-    // The specific accumulator is determined below in the
-    // conversion to a test harness.
-    // TODO: Move this parameter elsewhere
+    // The type here is the type of data between the
+    // two computations (output from 1 = input to 2),
+    // the tokens made by the lexer from the input.
     comp: Compute2::<_,_,_,EvalVec<Token,StdRng>,_>::new(
-    	MFolder::new(
+    	Lexer::new(
     		name_of_string(String::from("tokenize")),
-				(IFaceNew::new(),None),
-				tokenize_step,
-				tokenize_meta,
-				|a|{
-					let ts = tokenize_final(a);
-					ns(name_to_tree.clone(),||{IncrementalFrom{
-						data: ts, // This determines the accumulator type
-			      datagauge: datagauge,
-			      namegauge: namegauge,
-			      coord: coord.clone(),
-					}.create(&mut StdRng::new().unwrap()).1})
-				},
-			),
+    		adder_dfa(),
+    	),
     	MFolder::new(
     		name_of_string(String::from("parse")),
     		IFaceNew::new(),
@@ -0,0 +1,175 @@
+//! This is a system for creating charts of the
+//! cost of moving through a raz, as the number of
+//! elements per leaf grows
+//!
+//! Focusing and unfocusing share the data of leaves and
+//! archives, so their time should not depend on the gauge.
+//! For comparison, this also times the copies that focusing and
+//! unfocusing used to make, of one leaf into the stacks of a raz
+//! and back out into a new leaf.
+
+extern crate rand;
+extern crate time;
+#[macro_use] extern crate clap;
+extern crate adapton;
+extern crate iodyn;
+
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::rc::Rc;
+use rand::{Rng,StdRng,SeedableRng};
+use time::Duration;
+use iodyn::raz::{Raz,RazTree};
+use iodyn::raz_meta::Count;
+use adapton::engine::*;
+use adapton::engine::manage::*;
+
+const DEFAULT_DATASEED: usize = 0;
+const DEFAULT_EDITSEED: usize = 0;
+const DEFAULT_SIZE: usize = 1000000;
+const DEFAULT_UNITSIZE: usize = 10;
+const DEFAULT_COUNT: usize = 9;
+const DEFAULT_FOCUSES: usize = 1000;
+
+fn build(size: usize, gauge: usize, rng: &mut StdRng) -> RazTree<usize,Count> {
+  let mut raz = Raz::new();
+  for i in 0..size {
+    raz.push_left(rng.gen());
+    if i % gauge == gauge - 1 {
+      raz.archive_left(iodyn::inc_level(), Some(name_of_usize(i)));
+    }
+  }
+  raz.unfocus()
+}
+
+fn main() {
+  //command-line
+  let args = clap::App::new("focus_copy")
+    .version("0.1")
+    .about("Charts the time to refocus a RAZ as leaves grow")
+    .args_from_usage("\
+      --dataseed=[dataseed]			'seed for random data'
+      --editseed=[edit_seed]    'seed for random focus points'
+      -s, --size=[size]         'sequence length'
+      -g, --unitsize=[unitsize] 'smallest elements per leaf'
+      -c, --count=[count]       'number of runs, by default each 2 is 10x larger'
+      -f, --focuses=[focuses]   'refocusing operations per run'
+      -o, --outfile=[outfile]   'name for output files (of different extensions)' ")
+    .get_matches();
+  let dataseed = value_t!(args, "dataseed", usize).unwrap_or(DEFAULT_DATASEED);
+  let editseed = value_t!(args, "editseed", usize).unwrap_or(DEFAULT_EDITSEED);
+  let size = value_t!(args, "size", usize).unwrap_or(DEFAULT_SIZE);
+  let unitsize = value_t!(args, "unitsize", usize).unwrap_or(DEFAULT_UNITSIZE);
+  let count = value_t!(args, "count", usize).unwrap_or(DEFAULT_COUNT);
+  let focuses = value_t!(args, "focuses", usize).unwrap_or(DEFAULT_FOCUSES);
+  let outfile = args.value_of("outfile");
+
+  let multiplier = f32::sqrt(10.0);
+
+  let _ = init_dcg(); assert!(engine_is_dcg());
+
+  // run experiments
+  let mut data_rng = StdRng::from_seed(&[dataseed]);
+  let mut edit_rng = StdRng::from_seed(&[editseed]);
+  let mut results: Vec<(usize,i64,i64,i64)> = Vec::new();
+  for i in 0..count {
+    let gauge = (unitsize as f32 * multiplier.powi(i as i32)) as usize;
+    let mut tree = build(size, gauge, &mut data_rng);
+    let points: Vec<usize> = (0..focuses).map(|_| edit_rng.gen::<usize>() % size).collect();
+
+    // focus and unfocus, sharing the leaf
+    let refocus = Duration::span(||{
+      for &p in points.iter() {
+        tree = tree.clone().focus(p).unwrap().unfocus();
+      }
+    });
+
+    // move through the sequence, sharing leaves and archives
+    let mut raz = tree.clone().focus(0usize).unwrap();
+    let moves = Duration::span(||{
+      for &p in points.iter() { raz.seek(p); }
+    });
+
+    // the old focus, copying one leaf's data element by element into
+    // new vectors for the stacks, reversed on the right, and the old
+    // unfocus, copying it back out into a new leaf
+    let leaf = Rc::new((0..gauge).collect::<Vec<usize>>());
+    let copies = Duration::span(||{
+      for &p in points.iter() {
+        let split = p % gauge;
+        // focus
+        let mut l = Vec::new();
+        for e in leaf[..split].iter() { l.push(*e) }
+        let mut r = Vec::new();
+        for e in leaf[split..].iter().rev() { r.push(*e) }
+        // unfocus
+        let mut rebuilt = Vec::new();
+        for e in l.iter() { rebuilt.push(*e) }
+        for e in r.iter().rev() { rebuilt.push(*e) }
+        let rebuilt = Rc::new(rebuilt);
+        assert_eq!(*leaf, *rebuilt);
+      }
+    });
+
+    results.push((
+      gauge,
+      refocus.num_nanoseconds().unwrap() / focuses as i64,
+      moves.num_nanoseconds().unwrap() / focuses as i64,
+      copies.num_nanoseconds().unwrap() / focuses as i64,
+    ));
+  }
+
+  println!("time per op in ns (gauge,refocus,seek,copy): {:?}", results);
+
+  ///////
+  // Draft of output generation
+  ///////
+
+  let filename = if let Some(f) = outfile {f} else {"out"};
+
+  let mut dat: Box<Write> =
+    Box::new(
+      OpenOptions::new()
+      .create(true)
+      .write(true)
+      .truncate(true)
+      .open(filename.to_owned()+".dat")
+      .unwrap()
+    )
+  ;
+
+  // generate data file
+  writeln!(dat,"'{}'\t'{}'\t'{}'\t'{}'","Gauge","Refocus Time","Seek Time","Copy Time").unwrap();
+  for &(gauge,refocus,seek,copy) in results.iter() {
+    writeln!(dat,"{}\t{}\t{}\t{}",gauge,
+      refocus as f64 / 1_000.0,
+      seek as f64 / 1_000.0,
+      copy as f64 / 1_000.0,
+    ).unwrap();
+  }
+
+  let mut plotscript =
+    OpenOptions::new()
+    .create(true)
+    .write(true)
+    .truncate(true)
+    .open(filename.to_owned()+".plotscript")
+    .unwrap()
+  ;
+
+  writeln!(plotscript,"set terminal pdf").unwrap();
+  writeln!(plotscript,"set logscale xy").unwrap();
+  writeln!(plotscript,"set output '{}'", filename.to_owned()+".pdf").unwrap();
+  write!(plotscript,"set title \"{}", "Time to Refocus as Leaves Grow\\n").unwrap();
+  writeln!(plotscript,"size: {}, focuses: {}\"",size,focuses).unwrap();
+  writeln!(plotscript,"set xlabel '{}'", "gauge").unwrap();
+  writeln!(plotscript,"set ylabel '{}'","Time(us)").unwrap();
+  writeln!(plotscript,"set key left top box").unwrap();
+  writeln!(plotscript,"plot \\").unwrap();
+  writeln!(plotscript,"'{}' i 0 u 1:2 t '{}' with lines,\\",filename.to_owned()+".dat","Focus and unfocus").unwrap();
+  writeln!(plotscript,"'{}' i 0 u 1:3 t '{}' with lines,\\",filename.to_owned()+".dat","Seek").unwrap();
+  writeln!(plotscript,"'{}' i 0 u 1:4 t '{}' with lines,\\",filename.to_owned()+".dat","Copying focus, as before").unwrap();
+
+  ::std::process::Command::new("gnuplot").arg(filename.to_owned()+".plotscript").output().unwrap();
+
+}
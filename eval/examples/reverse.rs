@@ -61,8 +61,6 @@ fn main2() {
   let do_trace = args.is_present("trace");
 	let coord = StdRng::from_seed(&[dataseed]);
 
-  let name_reverse = name_of_string(String::from("reverse"));
-
   let mut test_inc = EditComputeSequence{
     init: IncrementalInit {
       size: start_size,
@@ -71,28 +69,7 @@ fn main2() {
       coord: coord.clone(),
     },
     edit: BatchInsert(edits),
-    comp: TreeFoldG::new(
-      |v|{
-        let mut c = v.clone();
-        c.reverse();
-        match IRazTree::from_vec(c) {
-          None => IRazTree::empty(),
-          Some(t) => t,
-        }
-      },
-      move|t1,lev,nm,t2|{
-        match (t1.is_empty(),t2.is_empty()) {
-          (true,true) => IRazTree::empty(),
-          (false,true) => t1,
-          (true,false) => t2,
-          (false,false) => {
-            ns(name_reverse.clone(),||{
-              IRazTree::join(t2,lev,nm,t1).unwrap()
-            })
-          },
-        }
-      },
-    ),
+    comp: Reverse,
     changes: changes,
   };
 
@@ -104,13 +81,7 @@ fn main2() {
       coord: coord.clone(),
     },
     edit: BatchInsert(edits),
-    comp: Native::new(
-      |v:&Vec<_>|{
-        let mut c = v.clone();
-        c.reverse();
-        c
-      },
-    ),
+    comp: Reverse,
     changes: changes,
   };
 
@@ -4,6 +4,7 @@ use rand::{Rng,StdRng};
 use time::Duration;
 use primitives::*;
 use adapton::engine::{Name,ns,name_fork};
+use iodyn::lex::Dfa;
 
 pub trait Creator<R,D> {
 	fn create(&mut self, rnd: &mut StdRng) -> (R,D);
@@ -449,6 +450,33 @@ where
 	}
 }
 
+/// Tokenizes the characters of a sequence with `iodyn::lex`
+pub struct Lexer<T>{
+	name: Name,
+	dfa: Rc<Dfa<T>>,
+}
+impl<T> Lexer<T> {
+	pub fn new(name:Name,dfa:Dfa<T>) -> Self {
+		Lexer{name: name, dfa: Rc::new(dfa)}
+	}
+}
+impl<T,D:CompLex<T>>
+Computor<Duration,D> for Lexer<T> {
+	fn compute(&mut self, data: &D, rng: &mut StdRng) -> Duration {
+		let (time,answer) = ns(self.name.clone(),||{data.comp_lex(self.dfa.clone(),rng)});
+		#[allow(unused)]
+		let saver = Vec::new().push(answer); // don't let rust compile this away
+		time
+	}
+}
+impl<T,D:CompLex<T>>
+Computor<(Vec<Duration>,D::Target),D> for Lexer<T> {
+	fn compute(&mut self, data: &D, rng: &mut StdRng) -> (Vec<Duration>,D::Target) {
+		let (time,answer) = ns(self.name.clone(),||{data.comp_lex(self.dfa.clone(),rng)});
+		(vec![time],answer)
+	}
+}
+
 pub struct HFolder<
 	A:Clone, E, M, T, O, R:Fn(A,&E)->A, RF:Fn(A,Option<Name>)->A, N:Fn(A,M)->A, F:Fn(T)->O
 >{
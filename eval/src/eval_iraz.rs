@@ -1,5 +1,4 @@
 //use std::fmt::{self,Debug};
-use std::cmp;
 use std::rc::Rc;
 use rand::{Rng,StdRng,Rand};
 use time::Duration;
@@ -8,6 +7,7 @@ use iodyn::memo::MemoFrom;
 use iodyn::{IRaz, IRazTree};
 use iodyn::archive_stack::{AtTail};
 use iodyn::tree_cursor::gen_level;
+use iodyn::lex::{self,Dfa};
 use primitives::*;
 use interface::{Adapt};
 
@@ -245,58 +245,24 @@ CompMax for EvalIRaz<E,G> {
 		let clone = self.raztree.clone().unwrap();
 		let mut max_val = None;
 		let time = Duration::span(||{
-	    	max_val = Some(clone.fold_up(Rc::new(|e:&E|e.clone()),Rc::new(|e1:E,e2:E|cmp::max(e1,e2))))
+	    	max_val = Some(clone.max_by(Rc::new(|e1:&E,e2:&E|e1.cmp(e2))))
 		});
 		(time,max_val.unwrap())
 	}
 }
 
-// Inefficient - update to fold_up_gauged
-// impl<E:Adapt,G:Rng>
-// CompRev for EvalIRaz<E,G> {
-// 	type Target = IRazTree<E>;
-// 	fn comp_rev(&self, _rng: &mut StdRng) -> (Duration,Self::Target) {
-// 	  #[derive(Debug,Eq,PartialEq,Hash,Clone)]
-// 	  enum R<E:Adapt>{
-// 	    I(E),
-// 	    V(Vec<E>),
-// 	    T(IRazTree<E>)
-// 	  }
-// 	  let name_rev = name_of_string(String::from("reverse"));
-// 		let clone = self.raztree.clone().unwrap();
-// 		let mut revraz = None;
-// 		let init = Rc::new(|a:&E|{R::I(a.clone())});
-// 		let to_vec = Rc::new(|l,r|{
-// 			match (l,r) {
-// 				(R::I(e1),R::I(e2)) => R::V(vec![e1,e2]),
-// 				(R::V(mut v),R::I(e)) => {v.push(e);R::V(v)},
-// 				_ => unreachable!(),
-// 			}
-// 		});
-// 		let to_tree = Rc::new(move|l,lv,n:Option<Name>,r| {
-// 			let ltree = match l {
-// 				R::I(e) => IRazTree::from_vec(vec![e]).unwrap(),
-// 				R::V(mut v) => {v.reverse();IRazTree::from_vec(v).unwrap()},
-// 				R::T(r) => r,
-// 			};
-// 			let rtree = match r {
-// 				R::I(e) => IRazTree::from_vec(vec![e]).unwrap(),
-// 				R::V(mut v) => {v.reverse();IRazTree::from_vec(v).unwrap()},
-// 				R::T(r) => r,
-// 			};
-// 			R::T(ns(name_rev.clone(),||{IRazTree::join(rtree,lv,n,ltree).unwrap()}))
-// 		});
-// 		let time = Duration::span(||{
-// 	    	revraz = Some(match clone.fold_up_nl(init, to_vec, to_tree) {
-// 	    		None => IRazTree::empty(),
-// 	    		Some(R::I(e)) => IRazTree::from_vec(vec![e]).unwrap(),
-// 	    		Some(R::V(mut v)) => {v.reverse(); IRazTree::from_vec(v).unwrap()},
-// 	    		Some(R::T(t)) => t,
-// 	    	});
-// 		});
-// 		(time,revraz.unwrap())
-// 	}
-// }
+impl<E:Adapt,G:Rng>
+CompRev for EvalIRaz<E,G> {
+	type Target = IRazTree<E>;
+	fn comp_rev(&self, _rng: &mut StdRng) -> (Duration,Self::Target) {
+		let clone = self.raztree.clone().unwrap();
+		let mut revraz = None;
+		let time = Duration::span(||{
+	    	revraz = Some(clone.reverse())
+		});
+		(time,revraz.unwrap())
+	}
+}
 
 impl<E:Adapt,O:Adapt,I,B,G:Rng>
 CompTreeFold<E,O,I,B> for EvalIRaz<E,G> where
@@ -411,3 +377,16 @@ CompFoldArchive<E,O,u32,F,FF,N> for EvalIRaz<E,G> where
 	}
 }
 
+
+impl<E:Adapt+Into<char>,T:Adapt,G:Rng+Clone>
+CompLex<T> for EvalIRaz<E,G> {
+	type Target = EvalIRaz<T,G>;
+	fn comp_lex(&self, dfa: Rc<Dfa<T>>, _rng: &mut StdRng) -> (Duration,Self::Target) {
+		let clone = self.raztree.clone().unwrap();
+		let mut eval = EvalIRaz::new(self.datagauge, self.namegauge, self.coord.clone());
+		let time = Duration::span(||{
+			eval.raztree = Some(lex::lex(clone,dfa));
+		});
+		(time, eval)
+	}
+}
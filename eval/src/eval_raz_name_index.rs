@@ -152,9 +152,9 @@ EditInsert for EvalRazNameIndex<E,G> {
 	fn insert(mut self, batch_size: usize, rng: &mut StdRng) -> (Duration,Self) {
 		let tree = self.raztree.take().unwrap_or_else(||panic!("raz uninitialized"));
 		let loc = {
-			let mut names = tree.meta().0.keys();
+			let mut names = tree.meta().names();
 			let name_count = names.len();
-			names.nth(self.coord.gen::<usize>() % name_count).unwrap().clone()
+			names.swap_remove(self.coord.gen::<usize>() % name_count)
 		};
 		let mut focus = None;
 		let focus_time = Duration::span(||{
@@ -2,6 +2,7 @@ use std::fmt::{self,Debug};
 use std::rc::Rc;
 use rand::{StdRng,Rng,Rand};
 use time::Duration;
+use iodyn::lex::{Dfa,LexState};
 use primitives::*;
 
 /// Test harness for `Vec`
@@ -192,3 +193,20 @@ CompFoldMeta<E,O,M,F,N> for EvalVec<E,G> where
 	}
 }
 
+
+impl<E:Clone+Into<char>,T,G:Rng+Clone>
+CompLex<T> for EvalVec<E,G> {
+	type Target = EvalVec<T,G>;
+	fn comp_lex(&self, dfa: Rc<Dfa<T>>, _rng: &mut StdRng) -> (Duration,Self::Target) {
+		let mut tokens = Vec::new();
+		let time = Duration::span(||{
+			let lex = self.vec.iter().fold(LexState::start(),|lex,e|{
+				let (lex,tok) = dfa.step(lex,e.clone().into());
+				tokens.extend(tok);
+				lex
+			});
+			tokens.extend(dfa.finish(lex));
+		});
+		(time, EvalVec{vec:tokens,coord:self.coord.clone()})
+	}
+}
@@ -2,6 +2,7 @@ use std::rc::Rc;
 use rand::{Rng,StdRng};
 use time::Duration;
 use adapton::engine::*;
+use iodyn::lex::Dfa;
 
 /// empty initialization of an incremental collection test harness
 pub trait CreateEmpty<G:Rng> {
@@ -82,3 +83,9 @@ pub trait CompFoldArchive<I,O,M,B:Fn(O,&I)->O,FB:Fn(O,Option<Name>)->O,N:Fn(O,M)
 	type Target;
 	fn comp_fold_archive(&self, accum: O, b:Rc<B>, fb:Rc<FB>, m:Rc<N>, rng: &mut StdRng) -> (Duration,Self::Target);
 }
+
+/// tokenizes a sequence of characters with the automaton
+pub trait CompLex<T> {
+	type Target;
+	fn comp_lex(&self, dfa: Rc<Dfa<T>>, rng: &mut StdRng) -> (Duration,Self::Target);
+}
@@ -7,15 +7,21 @@
 //!
 //! - a stack implemented with both persistent and mutable components
 //! - insert in low-const O(1) - rare reallocations
-//! - copy in high-const O(1) - short array copy then pointer
+//! - copy in O(1) - pointer copies of shared chunks
 //!
 //! The archive stack is implemented as a persistent stack of vectors.
 //! Use `archive()` to save the current vector as the first entry in
 //! the persistent linked list.
+//!
+//! Vectors are kept in shared `Chunk`s, so archiving, opening archives
+//! and moving whole chunks between stacks and trees don't copy elements.
+//! Elements are copied only when a chunk that is shared is changed.
 
 use std::mem;
-use std::fmt::Debug;
-use std::hash::Hash;
+use std::rc::Rc;
+use std::ops::Range;
+use std::fmt::{self,Debug};
+use std::hash::{Hash,Hasher};
 use stack::Stack;
 use adapton::engine::Name;
 
@@ -33,10 +39,52 @@ use adapton::engine::Name;
 
 #[derive(Debug,PartialEq,Eq,Clone,Hash)]
 pub struct AStack<E:'static+Debug+Clone+Eq+Hash,M:'static+Debug+Clone+Eq+Hash> {
-	// OPTIMIZE: Keeping separate vecs requires copying data when archiving
-	// eventually we need to refactor to avoid this
-	current: Vec<E>,
-	archived: Stack<(M,Vec<E>)>,
+	current: Chunk<E>,
+	archived: Stack<(M,Chunk<E>)>,
+}
+
+/// Summary of an archive stack, from `AStack::stats`
+#[derive(Clone,Copy,PartialEq,Eq,Debug,Default)]
+pub struct AStackStats {
+	/// elements in the active vector
+	pub active: usize,
+	/// number of archived vectors
+	pub archives: usize,
+	/// number of elements, including the active vector
+	pub elements: usize,
+	/// elements in the smallest archived vector
+	pub min_archive: usize,
+	/// elements in the largest archived vector
+	pub max_archive: usize,
+	/// archives with names
+	pub named: usize,
+	/// archives without names
+	pub unnamed: usize,
+	/// rough size of the elements and archive nodes in bytes, counting
+	/// shared chunks once for each use, and not counting names
+	pub bytes: usize,
+}
+
+impl AStackStats {
+	/// average elements per archived vector
+	pub fn avg_archive(&self) -> f64 {
+		if self.archives == 0 { 0.0 } else { (self.elements - self.active) as f64 / self.archives as f64 }
+	}
+}
+
+/// A shared run of elements
+///
+/// A view of part of a reference-counted vector, in either direction.
+/// Chunks are cloned, narrowed, reversed and rejoined without copying
+/// elements. Changing a chunk copies its elements first, unless it is
+/// the only view of its whole vector.
+#[derive(Clone)]
+pub struct Chunk<E> {
+	data: Rc<Vec<E>>,
+	start: usize,
+	end: usize,
+	// elements run from the end of the range to the start
+	rev: bool,
 }
 
 /// Marker type for interpreting the stack as a sequence.
@@ -56,12 +104,184 @@ pub struct AtTail<T: 'static+Debug+Clone+Eq+Hash,M:'static+Debug+Clone+Eq+Hash>(
 	pub AStack<T,M>
 );
 
+impl<E: Clone> Chunk<E> {
+	/// new empty chunk
+	pub fn new() -> Self { Chunk::from(Vec::new()) }
+
+	/// new empty chunk, with room for `capacity` elements
+	pub fn with_capacity(capacity: usize) -> Self { Chunk::from(Vec::with_capacity(capacity)) }
+
+	/// the number of elements in the chunk
+	pub fn len(&self) -> usize { self.end - self.start }
+
+	/// whether the chunk has no elements
+	pub fn is_empty(&self) -> bool { self.start == self.end }
+
+	fn index(&self, i: usize) -> usize {
+		if self.rev { self.end - 1 - i } else { self.start + i }
+	}
+
+	/// the element at position `i`
+	pub fn get(&self, i: usize) -> Option<&E> {
+		if i < self.len() { Some(&self.data[self.index(i)]) } else { None }
+	}
+
+	/// the last element
+	pub fn last(&self) -> Option<&E> {
+		if self.is_empty() { None } else { self.get(self.len() - 1) }
+	}
+
+	/// iterates over the elements in order
+	pub fn iter<'a>(&'a self) -> impl DoubleEndedIterator<Item=&'a E> + 'a {
+		(0..self.len()).map(move |i| &self.data[self.index(i)])
+	}
+
+	/// the elements at the positions in `range`, sharing this chunk's data
+	pub fn slice(&self, range: Range<usize>) -> Self {
+		assert!(range.start <= range.end && range.end <= self.len(), "chunk slice out of bounds");
+		let (start, end) = if self.rev {
+			(self.end - range.end, self.end - range.start)
+		} else {
+			(self.start + range.start, self.start + range.end)
+		};
+		Chunk{data: self.data.clone(), start, end, rev: self.rev}
+	}
+
+	/// the elements in reverse order, sharing this chunk's data
+	pub fn reversed(mut self) -> Self {
+		self.rev = !self.rev;
+		self
+	}
+
+	/// the elements of `self` followed by those of `other`
+	///
+	/// Two parts of the same vector that meet in the same direction
+	/// are joined without copying
+	pub fn append(mut self, other: Self) -> Self {
+		if other.is_empty() { return self }
+		if self.is_empty() { return other }
+		if Rc::ptr_eq(&self.data, &other.data) && self.rev == other.rev {
+			if !self.rev && self.end == other.start {
+				self.end = other.end;
+				return self
+			}
+			if self.rev && other.end == self.start {
+				self.start = other.start;
+				return self
+			}
+		}
+		self.extend(other.iter().cloned());
+		self
+	}
+
+	/// copies the elements into a new vector
+	pub fn to_vec(&self) -> Vec<E> { self.iter().cloned().collect() }
+
+	/// the elements as a vector, copying them if they are shared
+	pub fn into_vec(self) -> Vec<E> {
+		if self.is_whole() {
+			match Rc::try_unwrap(self.data) {
+				Ok(vec) => vec,
+				Err(data) => (*data).clone(),
+			}
+		} else { self.to_vec() }
+	}
+
+	/// the elements as a shared vector, copying them unless the
+	/// chunk is a whole vector in its original order
+	pub fn into_rc(self) -> Rc<Vec<E>> {
+		if self.is_whole() { self.data } else { Rc::new(self.to_vec()) }
+	}
+
+	fn is_whole(&self) -> bool {
+		!self.rev && self.start == 0 && self.end == self.data.len()
+	}
+
+	// the elements as a vector that can be changed in place
+	fn make_mut(&mut self) -> &mut Vec<E> {
+		if !self.is_whole() || Rc::get_mut(&mut self.data).is_none() {
+			let mut vec = Vec::with_capacity(self.len() * 2);
+			vec.extend(self.iter().cloned());
+			*self = Chunk::from(vec);
+		}
+		Rc::get_mut(&mut self.data).unwrap()
+	}
+
+	/// adds an element to the end of the chunk
+	pub fn push(&mut self, elm: E) {
+		self.make_mut().push(elm);
+		self.end += 1;
+	}
+
+	/// removes and returns the last element of the chunk
+	///
+	/// The element is cloned if the data is shared
+	pub fn pop(&mut self) -> Option<E> {
+		if self.is_empty() { return None }
+		if self.is_whole() {
+			if let Some(vec) = Rc::get_mut(&mut self.data) {
+				self.end -= 1;
+				return vec.pop()
+			}
+		}
+		let elm = self.last().cloned();
+		if self.rev { self.start += 1 } else { self.end -= 1 }
+		elm
+	}
+}
+
+impl<E: Clone> Extend<E> for Chunk<E> {
+	fn extend<I: IntoIterator<Item=E>>(&mut self, iter: I) {
+		let vec = self.make_mut();
+		vec.extend(iter);
+		let len = vec.len();
+		self.end = len;
+	}
+}
+
+impl<E> From<Vec<E>> for Chunk<E> {
+	fn from(vec: Vec<E>) -> Self {
+		let end = vec.len();
+		Chunk{data: Rc::new(vec), start: 0, end, rev: false}
+	}
+}
+
+impl<E> From<Rc<Vec<E>>> for Chunk<E> {
+	fn from(data: Rc<Vec<E>>) -> Self {
+		let end = data.len();
+		Chunk{data, start: 0, end, rev: false}
+	}
+}
+
+impl<E: Clone> Default for Chunk<E> {
+	fn default() -> Self { Self::new() }
+}
+
+// chunks are compared and hashed by their elements
+impl<E: Clone+PartialEq> PartialEq for Chunk<E> {
+	fn eq(&self, other: &Self) -> bool {
+		self.len() == other.len() && self.iter().eq(other.iter())
+	}
+}
+impl<E: Clone+Eq> Eq for Chunk<E> {}
+impl<E: Clone+Hash> Hash for Chunk<E> {
+	fn hash<H: Hasher>(&self, state: &mut H) {
+		self.len().hash(state);
+		for e in self.iter() { e.hash(state) }
+	}
+}
+impl<E: Clone+Debug> Debug for Chunk<E> {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		f.debug_list().entries(self.iter()).finish()
+	}
+}
+
 impl<E:'static+Debug+Clone+Eq+Hash, M:'static+Debug+Clone+Eq+Hash>
 AStack<E,M> {
 	/// new `AStack` with a new vector as current stack
 	pub fn new() -> Self {
 		AStack {
-			current: Vec::new(),
+			current: Chunk::new(),
 			archived: Stack::new(),
 		}
 	}
@@ -69,7 +289,7 @@ AStack<E,M> {
 	/// new `AStack` with a new pre-allocated vector as current stack
 	pub fn with_capacity(capacity: usize) -> Self {
 		AStack {
-			current: Vec::with_capacity(capacity),
+			current: Chunk::with_capacity(capacity),
 			archived: Stack::new(),
 		}
 	}
@@ -105,37 +325,58 @@ AStack<E,M> {
 	// extend the backing vector
 	#[doc(hidden)]
 	pub fn extend(&mut self, extra: &[E]) {
-		self.current.extend_from_slice(extra);
+		self.current.extend(extra.iter().cloned());
 	}
 
 	// specialized use that will be removed in future updates
 	#[doc(hidden)]
 	pub fn extend_rev(&mut self, extra: &[E]) {
-		self.current.extend_from_slice(extra);
-		self.current.reverse();
+		self.extend(extra);
+		let current = mem::take(&mut self.current);
+		self.current = current.reversed();
+	}
+
+	/// add the elements of a chunk to the active vector
+	///
+	/// If the active vector is empty, it becomes the chunk, without
+	/// copying its elements
+	pub fn push_chunk(&mut self, chunk: Chunk<E>) {
+		let current = mem::take(&mut self.current);
+		self.current = current.append(chunk);
 	}
 
 	/// Exposes the last data archived, returning the
 	/// prior active vector and the metadata stored with the archive
 	pub fn next_archive(&mut self) -> Option<(Vec<E>,Option<M>)> {
+		self.next_chunk().map(|(chunk,meta)| (chunk.into_vec(), meta))
+	}
+
+	/// Like `next_archive`, but returns the active vector as a chunk,
+	/// without copying it
+	pub fn next_chunk(&mut self) -> Option<(Chunk<E>,Option<M>)> {
 		if self.is_empty() { return None }
 		if self.archived.is_empty() {
-			let old_vec = mem::replace(&mut self.current, Vec::new());
+			let old_vec = mem::take(&mut self.current);
 			self.archived = Stack::new();
-			return Some((old_vec,None));
+			Some((old_vec,None))
 		} else {
 			let (old_meta,vec) = self.archived.peek().expect("missing data");
 			let old_vec = mem::replace(&mut self.current, vec);
 			self.archived = self.archived.pull().unwrap();
-			return Some((old_vec, Some(old_meta)));
+			Some((old_vec, Some(old_meta)))
 		}
 	}
 
 	/// peeks at the entire active vector
-	pub fn active_data(&self) -> &Vec<E> {
+	pub fn active_data(&self) -> &Chunk<E> {
 		&self.current
 	}
 
+	/// copies the entire active vector
+	pub fn active_vec(&self) -> Vec<E> {
+		self.current.to_vec()
+	}
+
 	/// peeks at the element at the top of the stack, even if it
 	/// is within the archive.
 	///
@@ -160,11 +401,31 @@ AStack<E,M> {
 	/// a capacity for the new active vector
 	pub fn archive_with_capacity(&mut self, name: Option<Name>, meta: M, capacity: usize) -> bool {
 		if self.current.len() == 0 { return false; }
-		let old_vec = mem::replace(&mut self.current, Vec::with_capacity(capacity));
+		let old_vec = mem::replace(&mut self.current, Chunk::with_capacity(capacity));
 		self.archived = self.archived.push(name,(meta, old_vec));
 		true
 	}
-	
+
+	/// a summary of the stack, in O(n) in the number of archives
+	pub fn stats(&self) -> AStackStats {
+		let list = self.archived.stats();
+		let mut stats = AStackStats{
+			active: self.current.len(),
+			archives: list.len,
+			elements: self.current.len(),
+			named: list.named,
+			unnamed: list.unnamed,
+			..AStackStats::default()
+		};
+		for (i,(_,chunk)) in self.archived.iter().enumerate() {
+			stats.min_archive = if i == 0 { chunk.len() } else { ::std::cmp::min(stats.min_archive, chunk.len()) };
+			stats.max_archive = ::std::cmp::max(stats.max_archive, chunk.len());
+			stats.elements += chunk.len();
+		}
+		stats.bytes = stats.elements * mem::size_of::<E>() + list.bytes;
+		stats
+	}
+
 	/// pulls the next vector from the archive if nessecary, returning
 	/// archived metadata. Panics if there are no elements left
 	fn retrieve(&mut self) -> Option<M> {
@@ -201,7 +462,7 @@ impl<E:'static+Debug+Clone+Eq+Hash>
 From<Vec<E>> for AStack<E,()> {
 	fn from(v: Vec<E>) -> Self {
 		AStack {
-			current: v,
+			current: Chunk::from(v),
 			archived: Stack::new(),
 		}
 	}
@@ -297,4 +558,63 @@ mod tests {
   	assert_eq!(vec![3,1,6,7,3,9,4,2,4], as_vec);
   }
 
+  #[test]
+  fn test_chunk() {
+  	let data = Rc::new((0..10).collect::<Vec<_>>());
+  	let whole = Chunk::from(data.clone());
+  	let l = whole.slice(0..4);
+  	let r = whole.slice(4..10).reversed();
+  	assert_eq!(vec![9,8,7,6,5,4], r.to_vec());
+  	assert_eq!(Some(&4), r.last());
+
+  	// the parts of a vector are joined without copying
+  	let joined = l.clone().append(r.clone().reversed());
+  	assert!(Rc::ptr_eq(&data, &joined.into_rc()));
+
+  	// changes copy shared data
+  	let mut l2 = l.clone();
+  	assert_eq!(Some(3), l2.pop());
+  	l2.push(20);
+  	assert_eq!(vec![0,1,2,20], l2.to_vec());
+  	assert_eq!(vec![0,1,2,3], l.to_vec());
+  	assert_eq!(l, Chunk::from(vec![0,1,2,3]));
+  }
+
+  #[test]
+  fn test_archive_shares() {
+  	let mut stack = AStack::new();
+  	stack.push_chunk(Chunk::from(vec![4,2,4]));
+  	assert_eq!(vec![4,2,4], stack.active_vec());
+  	assert_eq!(Chunk::from(vec![4,2,4]), *stack.active_data());
+  	stack.archive(Some(name_of_usize(1)),());
+  	stack.push(9);
+  	let mut copy = stack.clone();
+  	assert_eq!(Some(9), stack.pop());
+  	assert_eq!(Some(4), stack.pop());
+  	let (rest,_) = stack.next_chunk().unwrap();
+  	copy.next_chunk();
+  	let (archived,_) = copy.next_chunk().unwrap();
+  	assert_eq!(vec![4,2], rest.to_vec());
+  	assert_eq!(vec![4,2,4], archived.to_vec());
+  	assert!(Rc::ptr_eq(&rest.data, &archived.data));
+  }
+
+	#[test]
+	fn test_stats() {
+		let mut stack = AStack::new();
+		stack.extend(&[1,2,3,4]);
+		stack.archive(Some(name_of_usize(1)),());
+		stack.extend(&[5,6]);
+		stack.archive(None,());
+		stack.push(7);
+		let stats = stack.stats();
+		assert_eq!(AStackStats{
+			active: 1, archives: 2, elements: 7, min_archive: 2, max_archive: 4,
+			named: 1, unnamed: 1, bytes: stats.bytes,
+		}, stats);
+		assert_eq!(3.0, stats.avg_archive());
+		stack.pop(); stack.pop();
+		assert_eq!(1, stack.stats().archives);
+		assert_eq!(AStackStats::default(), AStack::<usize,()>::new().stats());
+	}
 }
@@ -0,0 +1,54 @@
+//! Articulation points for the incremental structures
+//!
+//! By default these are Adapton's `Art`s. With the `naive` cargo
+//! feature they are plain reference counted nodes, and memoized
+//! calls become direct recursion, for use of the collections as
+//! fast persistent structures without the incremental engine.
+
+#[cfg(not(feature = "naive"))]
+pub use adapton::engine::{Art,cell,put,force,force_map};
+
+#[cfg(feature = "naive")]
+pub use self::naive::{Art,cell,put,force,force_map};
+
+#[cfg(feature = "naive")]
+mod naive {
+	use std::fmt::Debug;
+	use std::hash::{Hash,Hasher};
+	use std::rc::Rc;
+	use adapton::engine::Name;
+
+	/// A shared, immutable node standing in for an `adapton` articulation
+	#[derive(Debug)]
+	pub struct Art<T>(Rc<T>);
+
+	impl<T> Clone for Art<T> {
+		fn clone(&self) -> Self { Art(self.0.clone()) }
+	}
+	impl<T: PartialEq> PartialEq for Art<T> {
+		fn eq(&self, other: &Self) -> bool {
+			Rc::ptr_eq(&self.0, &other.0) || self.0 == other.0
+		}
+	}
+	impl<T: Eq> Eq for Art<T> {}
+	impl<T: Hash> Hash for Art<T> {
+		fn hash<H: Hasher>(&self, state: &mut H) { self.0.hash(state) }
+	}
+
+	/// the name is ignored, there is no engine to store it in
+	pub fn cell<T: Hash+Eq+Debug+Clone+'static>(_name: Name, val: T) -> Art<T> {
+		Art(Rc::new(val))
+	}
+	pub fn put<T: Eq+Debug+Clone>(val: T) -> Art<T> {
+		Art(Rc::new(val))
+	}
+	pub fn force<T: Hash+Eq+Debug+Clone+'static>(art: &Art<T>) -> T {
+		(*art.0).clone()
+	}
+	pub fn force_map<T,S,F>(art: &Art<T>, mapf: F) -> S where
+		T: Hash+Eq+Debug+Clone+'static,
+		F: Fn(&Art<T>, T) -> S,
+	{
+		mapf(art, (*art.0).clone())
+	}
+}
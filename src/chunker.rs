@@ -0,0 +1,147 @@
+//! Content-Defined Chunking
+//!
+//! Decides archive points from the data itself, so that the same
+//! data is split the same way however it was built.
+//!
+//! - a rolling hash covers the last few elements, so a boundary
+//!   depends only on the data just before it
+//! - inserting or removing elements moves only the boundaries whose
+//!   window they fall in, later chunks keep their boundaries, levels
+//!   and names, and memoized results for them can be reused
+//! - chunks average about `gauge` elements, and are never longer
+//!   than four times that
+//!
+//! Feed elements in sequence order, with `Raz::push_left_chunked`
+//! or `RazTree::from_iter_chunked`, or by calling `push` directly.
+
+use std::collections::{HashMap,VecDeque};
+use std::hash::Hash;
+use adapton::engine::*;
+
+/// number of elements covered by the rolling hash, by default
+pub const DEFAULT_WINDOW: usize = 16;
+
+/// Rolling hash over a sequence, reporting chunk boundaries
+#[derive(Clone,Debug)]
+pub struct Chunker {
+	gauge: usize,
+	window: usize,
+	name_base: Option<Name>,
+	hashes: VecDeque<u64>,
+	roll: u64,
+	len: usize,
+	seen: HashMap<u64,usize>,
+}
+
+impl Chunker {
+	/// new chunker with the default window
+	///
+	/// if a name is given, each boundary is named from it, paired
+	/// with the rolling hash and the number of earlier boundaries
+	/// with the same hash
+	pub fn new(gauge: usize, name_base: Option<Name>) -> Self {
+		Chunker::with_window(gauge, DEFAULT_WINDOW, name_base)
+	}
+
+	/// new chunker hashing the last `window` elements
+	pub fn with_window(gauge: usize, window: usize, name_base: Option<Name>) -> Self {
+		assert!(gauge > 0, "gauge must be positive");
+		assert!(window > 0, "window must be positive");
+		Chunker {
+			gauge,
+			window,
+			name_base,
+			hashes: VecDeque::with_capacity(window),
+			roll: 0,
+			len: 0,
+			seen: HashMap::new(),
+		}
+	}
+
+	/// the average number of elements per chunk
+	pub fn gauge(&self) -> usize { self.gauge }
+
+	/// the number of elements since the last boundary
+	pub fn len(&self) -> usize { self.len }
+
+	/// true if no elements were pushed since the last boundary
+	pub fn is_empty(&self) -> bool { self.len == 0 }
+
+	/// takes the next element of the sequence
+	///
+	/// returns the level and name of an archive point if the chunk
+	/// ends after this element
+	pub fn push<E: Hash>(&mut self, elm: &E) -> Option<(u32,Option<Name>)> {
+		let h = ::hash64(elm);
+		self.roll = self.roll.rotate_left(1) ^ h;
+		self.hashes.push_back(h);
+		if self.hashes.len() > self.window {
+			let old = self.hashes.pop_front().unwrap();
+			self.roll ^= old.rotate_left(self.window as u32 % 64);
+		}
+		self.len += 1;
+		if !self.roll.is_multiple_of(self.gauge as u64) && self.len < self.gauge * 4 {
+			return None
+		}
+		self.len = 0;
+		let roll = self.roll;
+		let name = self.name_base.clone().map(|n| {
+			let count = self.seen.entry(roll).or_insert(0);
+			*count += 1;
+			name_pair(n, name_pair(name_of_hash64(roll), name_of_usize(*count - 1)))
+		});
+		Some((::hash_level(&roll), name))
+	}
+
+	/// forgets the elements pushed so far, and the names given
+	pub fn reset(&mut self) {
+		self.hashes.clear();
+		self.roll = 0;
+		self.len = 0;
+		self.seen.clear();
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn bounds(data: &[usize], chunker: &mut Chunker) -> Vec<(usize,u32)> {
+		data.iter().enumerate().filter_map(|(i,e)| {
+			chunker.push(e).map(|(lev,_)| (i,lev))
+		}).collect()
+	}
+
+	#[test]
+	fn test_stable_bounds() {
+		let data = (0..5000).map(|i| i * 7919 % 10007).collect::<Vec<usize>>();
+		let mut chunker = Chunker::new(32, None);
+		let before = bounds(&data, &mut chunker);
+		assert!(before.len() > 5000 / 128);
+		assert!(before.windows(2).all(|w| w[1].0 - w[0].0 <= 128));
+		chunker.reset();
+		assert_eq!(before, bounds(&data, &mut chunker));
+
+		// an insert shifts later boundaries by one, but keeps them
+		let mut edited = data.clone();
+		edited.insert(100, 42);
+		let after = bounds(&edited, &mut Chunker::new(32, None));
+		let shifted = after.iter().map(|&(i,lev)| (if i > 100 { i - 1 } else { i }, lev)).collect::<Vec<_>>();
+		let changed = shifted.iter().filter(|b| !before.contains(b)).count();
+		assert!(changed <= 2, "{} boundaries changed", changed);
+	}
+
+	#[test]
+	fn test_names() {
+		let mut chunker = Chunker::with_window(4, 2, Some(name_of_usize(0)));
+		let mut names = Vec::new();
+		for _ in 0..100 {
+			if let Some((_,n)) = chunker.push(&7usize) { names.push(n.unwrap()) }
+		}
+		assert!(chunker.len() < 16);
+		assert!(names.len() >= 100 / 16);
+		for (i,n) in names.iter().enumerate() {
+			assert!(!names[i+1..].contains(n));
+		}
+	}
+}
@@ -0,0 +1,249 @@
+//! Incremental Expression Evaluation
+//!
+//! Evaluates a sequence of tokens as an expression of associative
+//! binary operators with precedence, like `2 + 3 * 4`
+//!
+//! - the operators and operands are described by a `Grammar`
+//! - any run of tokens is summarized as an expression fragment,
+//!   and two fragments compose into the fragment of their tokens
+//! - fragments are combined bottom-up over a tree, so an edit
+//!   redoes only the compositions along the path to the root
+//!
+//! A fragment keeps the value of the complete operands between its
+//! first and last loosest operators, and the unfinished parts at
+//! either end. Its size depends on the number of precedence levels,
+//! not on the number of tokens.
+
+use std::rc::Rc;
+use std::fmt::Debug;
+use std::hash::Hash;
+
+use raz::RazTree;
+use raz_meta::RazMeta;
+use adapton::engine::*;
+
+type Operand<T,V> = Rc<dyn Fn(&T) -> Option<V>>;
+
+struct Level<T,V> {
+	is_op: Rc<dyn Fn(&T) -> bool>,
+	apply: Rc<dyn Fn(V,V) -> V>,
+}
+
+/// Operands and operators of an expression
+///
+/// Operators are added from the loosest binding to the tightest,
+/// and each must be associative. Tokens that are neither operands
+/// nor operators are ignored.
+pub struct Grammar<T,V> {
+	operand: Operand<T,V>,
+	levels: Vec<Level<T,V>>,
+}
+
+/// Summary of a run of tokens
+#[derive(Clone,Debug,PartialEq,Eq,Hash)]
+enum Frag<V: 'static+Debug+Clone+Eq+Hash> {
+	Empty,
+	// tokens that can't be part of an expression
+	Invalid,
+	Value(V),
+	// at least one operator of this level, and none looser
+	Ops{
+		level: usize,
+		head: Rc<Frag<V>>,
+		middle: Option<V>,
+		tail: Rc<Frag<V>>,
+	},
+}
+
+impl<T,V: 'static+Debug+Clone+Eq+Hash> Grammar<T,V> {
+	/// new grammar with no operators, where `operand` gives the
+	/// value of operand tokens
+	pub fn new<F>(operand: F) -> Self where
+		F: 'static + Fn(&T) -> Option<V>
+	{
+		Grammar{operand: Rc::new(operand), levels: Vec::new()}
+	}
+
+	/// adds an operator binding tighter than those added before
+	pub fn op<P,A>(&mut self, is_op: P, apply: A) where
+		P: 'static + Fn(&T) -> bool,
+		A: 'static + Fn(V,V) -> V,
+	{
+		self.levels.push(Level{is_op: Rc::new(is_op), apply: Rc::new(apply)});
+	}
+
+	/// Non-incremental value of an expression
+	///
+	/// Returns None if the expression is empty or malformed
+	pub fn eval(&self, tokens: &[T]) -> Option<V> {
+		let frag = tokens.iter().fold(Frag::Empty, |f,t| self.compose(f, self.frag(t)));
+		self.close(&frag)
+	}
+
+	fn frag(&self, token: &T) -> Frag<V> {
+		if let Some(v) = (self.operand)(token) { return Frag::Value(v) }
+		match self.levels.iter().position(|l| (l.is_op)(token)) {
+			None => Frag::Empty,
+			Some(level) => Frag::Ops{
+				level,
+				head: Rc::new(Frag::Empty),
+				middle: None,
+				tail: Rc::new(Frag::Empty),
+			},
+		}
+	}
+
+	// operands are tighter than any operator
+	fn level(&self, frag: &Frag<V>) -> usize {
+		match *frag {
+			Frag::Ops{level, ..} => level,
+			_ => self.levels.len(),
+		}
+	}
+
+	fn apply(&self, level: usize, l: Option<V>, r: Option<V>) -> Option<V> {
+		match (l,r) {
+			(None, v) | (v, None) => v,
+			(Some(l), Some(r)) => Some((self.levels[level].apply)(l, r)),
+		}
+	}
+
+	// the value of a fragment that is a complete expression
+	fn close(&self, frag: &Frag<V>) -> Option<V> {
+		match *frag {
+			Frag::Empty | Frag::Invalid => None,
+			Frag::Value(ref v) => Some(v.clone()),
+			Frag::Ops{level, ref head, ref middle, ref tail} => {
+				let head = self.close(head)?;
+				let tail = self.close(tail)?;
+				let left = self.apply(level, Some(head), middle.clone());
+				self.apply(level, left, Some(tail))
+			},
+		}
+	}
+
+	/// the fragment of `l` followed by `r`
+	fn compose(&self, l: Frag<V>, r: Frag<V>) -> Frag<V> {
+		let (ll, rl) = (self.level(&l), self.level(&r));
+		match (l, r) {
+			(Frag::Empty, f) | (f, Frag::Empty) => f,
+			(Frag::Invalid, _) | (_, Frag::Invalid) => Frag::Invalid,
+			(Frag::Value(_), Frag::Value(_)) => Frag::Invalid,
+			(Frag::Ops{level, head, middle, tail}, r) if ll < rl => Frag::Ops{
+				level, head, middle,
+				tail: Rc::new(self.compose((*tail).clone(), r)),
+			},
+			(l, Frag::Ops{level, head, middle, tail}) if rl < ll => Frag::Ops{
+				level, middle, tail,
+				head: Rc::new(self.compose(l, (*head).clone())),
+			},
+			(Frag::Ops{level, head, middle: m1, tail: t1}, Frag::Ops{head: h2, middle: m2, tail, ..}) => {
+				// the operand between the operators meeting here
+				match self.close(&self.compose((*t1).clone(), (*h2).clone())) {
+					None => Frag::Invalid,
+					Some(v) => {
+						let left = self.apply(level, m1, Some(v));
+						Frag::Ops{level, head, middle: self.apply(level, left, m2), tail}
+					},
+				}
+			},
+			_ => unreachable!(),
+		}
+	}
+}
+
+/// Incremental value of an expression
+///
+/// Fragments are kept for each subtree, in the namespace "expr".
+/// Returns None if the expression is empty or malformed.
+pub fn eval<T,V,M>(tokens: RazTree<T,M>, grammar: Rc<Grammar<T,V>>) -> Option<V> where
+	T: 'static + Debug+Clone+Eq+Hash,
+	V: 'static + Debug+Clone+Eq+Hash,
+	M: RazMeta<T>,
+{
+	let leaf_grammar = grammar.clone();
+	let bin_grammar = grammar.clone();
+	let frag = ns(name_of_string(String::from("expr")), || {
+		tokens.fold_up_gauged(Rc::new(move |vec: &Vec<T>| {
+			vec.iter().fold(Frag::Empty, |f,t| leaf_grammar.compose(f, leaf_grammar.frag(t)))
+		}), Rc::new(move |l, _lev, _n, r| {
+			bin_grammar.compose(l, r)
+		}))
+	});
+	frag.and_then(|f| grammar.close(&f))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use raz::Raz;
+	use raz_meta::Count;
+
+	#[derive(Clone,Debug,PartialEq,Eq,Hash)]
+	enum Tok {
+		Num(u64),
+		Plus,
+		Times,
+		Space,
+	}
+
+	fn arith() -> Grammar<Tok,u64> {
+		let mut g = Grammar::new(|t: &Tok| match *t { Tok::Num(n) => Some(n), _ => None });
+		g.op(|t| *t == Tok::Plus, |a,b| a + b);
+		g.op(|t| *t == Tok::Times, |a,b| a * b);
+		g
+	}
+
+	fn toks(text: &str) -> Vec<Tok> {
+		text.chars().map(|c| match c {
+			'+' => Tok::Plus,
+			'*' => Tok::Times,
+			' ' => Tok::Space,
+			c => Tok::Num(c.to_digit(10).unwrap() as u64),
+		}).collect()
+	}
+
+	#[test]
+	fn test_eval() {
+		let g = arith();
+		assert_eq!(Some(19), g.eval(&toks("2+3*4+5")));
+		assert_eq!(Some(120), g.eval(&toks("2*3*4*5")));
+		assert_eq!(Some(7), g.eval(&toks(" 7 ")));
+		assert_eq!(Some(31), g.eval(&toks("1*2 + 3*4*2 + 5")));
+		assert_eq!(None, g.eval(&toks("")));
+		assert_eq!(None, g.eval(&toks("2++3")));
+		assert_eq!(None, g.eval(&toks("2 3")));
+		assert_eq!(None, g.eval(&toks("*2")));
+		assert_eq!(None, g.eval(&toks("2+3*")));
+	}
+
+	#[test]
+	fn test_eval_tree() {
+		let g = Rc::new(arith());
+		// sums of products of four small numbers
+		let mut data = Vec::new();
+		for i in 0..300 {
+			if i > 0 { data.push(if i % 4 == 0 { Tok::Plus } else { Tok::Times }) }
+			data.push(Tok::Num(i % 3 + 1));
+		}
+		let mut r: Raz<Tok,Count> = Raz::new();
+		for (i,t) in data.iter().enumerate() {
+			r.push_left(t.clone());
+			if i % 5 == 2 { r.archive_left(::inc_level(),Some(name_of_usize(i))) }
+		}
+		let tree = r.unfocus();
+		let v = ns(name_of_string(String::from("first")),||{ eval(tree.clone(), g.clone()) });
+		assert!(v.is_some());
+		assert_eq!(g.eval(&data), v);
+
+		// change an operator in the middle
+		let mut r = tree.focus(301usize).unwrap();
+		assert_eq!(Some(Tok::Times), r.pop_right());
+		r.push_right(Tok::Plus);
+		data[301] = Tok::Plus;
+		let v = ns(name_of_string(String::from("edit")),||{ eval(r.unfocus(), g.clone()) });
+		assert_eq!(g.eval(&data), v);
+
+		assert_eq!(None, eval(RazTree::<Tok,Count>::empty(), g));
+	}
+}
@@ -0,0 +1,216 @@
+//! Frozen Sequences
+//!
+//! An immutable, thread-safe snapshot of a level tree sequence
+//!
+//! - `Send + Sync` when the elements are, for use by worker threads
+//! - keeps the levels of the original tree, and which branches were named
+//! - read-only iteration, indexing and folds, with no incremental engine
+//! - parallel folds that split work along the balanced level structure
+//!
+//! Adapton names can't be shared between threads, so a frozen
+//! sequence only records which branches had names. Create one with
+//! `RazTree::freeze()`, and import it back with `RazTree::thaw()`,
+//! which generates fresh names for those branches.
+
+use std::sync::Arc;
+use std::slice;
+use std::thread;
+
+#[derive(Debug)]
+pub(crate) enum Node<E> {
+	Leaf(Vec<E>),
+	Branch{
+		level: u32,
+		named: bool,
+		len: usize,
+		left: Option<Arc<Node<E>>>,
+		right: Option<Arc<Node<E>>>,
+	},
+}
+
+impl<E> Node<E> {
+	pub(crate) fn len(&self) -> usize {
+		match *self {
+			Node::Leaf(ref vec) => vec.len(),
+			Node::Branch{len, ..} => len,
+		}
+	}
+}
+
+/// Immutable sequence over `Arc` nodes
+#[derive(Debug)]
+pub struct FrozenSeq<E>(pub(crate) Option<Arc<Node<E>>>);
+
+impl<E> Clone for FrozenSeq<E> {
+	fn clone(&self) -> Self { FrozenSeq(self.0.clone()) }
+}
+
+impl<E> FrozenSeq<E> {
+	/// a sequence with no elements
+	pub fn empty() -> Self { FrozenSeq(None) }
+
+	pub(crate) fn leaf(vec: Vec<E>) -> Self {
+		FrozenSeq(Some(Arc::new(Node::Leaf(vec))))
+	}
+
+	pub(crate) fn branch(l: Self, level: u32, named: bool, r: Self) -> Self {
+		let len = l.len() + r.len();
+		FrozenSeq(Some(Arc::new(Node::Branch{
+			level, named, len, left: l.0, right: r.0,
+		})))
+	}
+
+	/// the number of elements in the sequence
+	pub fn len(&self) -> usize {
+		self.0.as_ref().map_or(0, |n| n.len())
+	}
+
+	pub fn is_empty(&self) -> bool { self.len() == 0 }
+
+	/// the level of the top branch, or 0 for leaves and empty sequences
+	pub fn level(&self) -> u32 {
+		match self.0.as_deref() {
+			Some(&Node::Branch{level, ..}) => level,
+			_ => 0,
+		}
+	}
+
+	/// the element at the given index
+	pub fn get(&self, mut index: usize) -> Option<&E> {
+		let mut node = match self.0 { None => return None, Some(ref n) => &**n };
+		loop { match *node {
+			Node::Leaf(ref vec) => return vec.get(index),
+			Node::Branch{ref left, ref right, ..} => {
+				let l_len = left.as_ref().map_or(0, |n| n.len());
+				let next = if index < l_len { left } else { index -= l_len; right };
+				match *next { None => return None, Some(ref n) => node = n }
+			},
+		}}
+	}
+
+	/// iterate over the elements, left to right
+	pub fn iter(&self) -> Iter<'_, E> {
+		let mut iter = Iter{ stack: Vec::new(), leaf: [].iter() };
+		if let Some(ref n) = self.0 { iter.descend(n) }
+		iter
+	}
+
+	/// fold over the sequence from leaves to root
+	///
+	/// The same computation as `RazTree::fold_up`, so the
+	/// operation must be associative. Returns None if there
+	/// are no elements.
+	pub fn fold_up<R,I,B>(&self, init: &I, bin: &B) -> Option<R> where
+		I: Fn(&E) -> R,
+		B: Fn(R,R) -> R,
+	{
+		self.0.as_ref().and_then(|n| fold_node(n, init, bin))
+	}
+
+	/// fold over the sequence from leaves to root, using up to
+	/// `threads` threads
+	///
+	/// Left and right branches are folded on separate threads, with
+	/// the thread budget split between them by element count. The
+	/// branches combined are the same as in `fold_up`, so results
+	/// are identical for any associative operation.
+	pub fn par_fold_up<R,I,B>(&self, threads: usize, init: Arc<I>, bin: Arc<B>) -> Option<R> where
+		E: 'static + Send + Sync,
+		R: 'static + Send,
+		I: 'static + Send + Sync + Fn(&E) -> R,
+		B: 'static + Send + Sync + Fn(R,R) -> R,
+	{
+		self.0.as_ref().and_then(|n| par_fold_node(n, threads, &init, &bin))
+	}
+}
+
+pub(crate) fn fold_node<E,R,I,B>(node: &Node<E>, init: &I, bin: &B) -> Option<R> where
+	I: Fn(&E) -> R,
+	B: Fn(R,R) -> R,
+{
+	match *node {
+		Node::Leaf(ref vec) => {
+			let mut iter = vec.iter().map(init);
+			iter.next().map(|first| iter.fold(first, bin))
+		},
+		Node::Branch{ref left, ref right, ..} => {
+			let l = left.as_ref().and_then(|n| fold_node(n, init, bin));
+			let r = right.as_ref().and_then(|n| fold_node(n, init, bin));
+			match (l,r) {
+				(None, None) => None,
+				(Some(r),None) | (None, Some(r)) => Some(r),
+				(Some(l),Some(r)) => Some(bin(l,r)),
+			}
+		},
+	}
+}
+
+fn par_fold_node<E,R,I,B>(node: &Arc<Node<E>>, threads: usize, init: &Arc<I>, bin: &Arc<B>) -> Option<R> where
+	E: 'static + Send + Sync,
+	R: 'static + Send,
+	I: 'static + Send + Sync + Fn(&E) -> R,
+	B: 'static + Send + Sync + Fn(R,R) -> R,
+{
+	if threads <= 1 { return fold_node(node, &**init, &**bin) }
+	match **node {
+		Node::Leaf(_) => fold_node(node, &**init, &**bin),
+		Node::Branch{ref left, ref right, len, ..} => {
+			// the left branch gets its own thread, the right stays on this one
+			let l_len = left.as_ref().map_or(0, |n| n.len());
+			let l_threads = ::std::cmp::min(threads - 1, ::std::cmp::max(1, threads * l_len / ::std::cmp::max(1, len)));
+			let r_threads = threads - l_threads;
+			let handle = left.clone().map(|l| {
+				let (init, bin) = (init.clone(), bin.clone());
+				thread::spawn(move || par_fold_node(&l, l_threads, &init, &bin))
+			});
+			let r = right.as_ref().and_then(|n| par_fold_node(n, r_threads, init, bin));
+			let l = handle.and_then(|h| h.join().expect("fold thread panicked"));
+			match (l,r) {
+				(None, None) => None,
+				(Some(r),None) | (None, Some(r)) => Some(r),
+				(Some(l),Some(r)) => Some(bin(l,r)),
+			}
+		},
+	}
+}
+
+/// Iterator over the elements of a `FrozenSeq`
+pub struct Iter<'a, E: 'a> {
+	// right branches still to visit
+	stack: Vec<&'a Node<E>>,
+	leaf: slice::Iter<'a, E>,
+}
+
+impl<'a, E> Iter<'a, E> {
+	fn descend(&mut self, mut node: &'a Node<E>) {
+		loop { match *node {
+			Node::Leaf(ref vec) => { self.leaf = vec.iter(); return },
+			Node::Branch{ref left, ref right, ..} => {
+				if let Some(ref r) = *right { self.stack.push(r) }
+				match *left {
+					Some(ref l) => node = l,
+					None => match self.stack.pop() { Some(n) => node = n, None => return },
+				}
+			},
+		}}
+	}
+}
+
+impl<'a, E> Iterator for Iter<'a, E> {
+	type Item = &'a E;
+	fn next(&mut self) -> Option<&'a E> {
+		loop {
+			if let Some(e) = self.leaf.next() { return Some(e) }
+			match self.stack.pop() {
+				None => return None,
+				Some(n) => self.descend(n),
+			}
+		}
+	}
+}
+
+impl<'a, E> IntoIterator for &'a FrozenSeq<E> {
+	type Item = &'a E;
+	type IntoIter = Iter<'a, E>;
+	fn into_iter(self) -> Iter<'a, E> { self.iter() }
+}
@@ -11,10 +11,13 @@
 use std::fmt::Debug;
 use std::hash::Hash;
 use std::rc::Rc;
+use std::collections::HashSet;
 use rand::Rng;
 
+#[cfg(not(feature = "naive"))]
 use adapton::macros::*;
 use adapton::engine::*;
+use art::{Art,cell,put,force};
 
 /// A persistent tree with stable, internally defined structure
 #[derive(Debug,PartialEq,Eq,Hash)]
@@ -212,21 +215,23 @@ Tree<E> {
 /// ```
 ///
 /// checks that the levels of the tree follow the convention
-/// of non-increasing to the left branch and decreasing to the
+/// of decreasing to the left branch and non-increasing to the
 /// right branch
 ///
+/// This is the convention of `Cursor::join` and `validate`.
+///
 /// also prints the levels of the failing trees and branches
 pub fn good_levels<E: Debug+Clone+Eq+Hash+'static>(tree: &Tree<E>) -> bool {
 	let mut good = true;
 	if let Some(ref t) = force(&tree.link).l_branch {
-		if t.level > tree.level {
+		if t.level >= tree.level {
 			println!("Tree with level {:?} has left branch with level {:?}", tree.level, t.level);
 			good = false;
 		}
 		if !good_levels(t) { good = false }
 	}
 	if let Some(ref t) = force(&tree.link).r_branch {
-		if t.level >= tree.level {
+		if t.level > tree.level {
 			println!("Tree with level {:?} has right branch with level {:?}", tree.level, t.level);
 			good = false;
 		}
@@ -235,6 +240,64 @@ pub fn good_levels<E: Debug+Clone+Eq+Hash+'static>(tree: &Tree<E>) -> bool {
 	good
 }
 
+/// A structural problem found while validating a tree
+///
+/// Some variants only apply to particular data types stored
+/// in the tree, and are reported by their own validators.
+#[derive(Debug,Clone,PartialEq,Eq,Hash)]
+pub enum Invalid {
+	/// a branch doesn't follow the level convention of its parent
+	Level{parent: u32, branch: u32, left: bool},
+	/// a name is used by more than one node
+	DuplicateName(Name),
+	/// a leaf with no data
+	EmptyLeaf,
+	/// placeholder data left in a finished tree
+	Dummy,
+	/// stored meta data differs from a recomputation from the branches
+	Meta{level: u32, name: Option<Name>},
+	/// a node marked clean is not the branch recorded in its parent
+	Dirty{level: u32, name: Option<Name>},
+}
+
+/// checks that a branch with level `branch` may sit below a
+/// node with level `parent`, on the given side
+pub fn check_level(parent: u32, branch: u32, left: bool) -> Option<Invalid> {
+	let good = if left { branch < parent } else { branch <= parent };
+	if good { None } else { Some(Invalid::Level{parent, branch, left}) }
+}
+
+impl<E: Debug+Clone+Eq+Hash+'static> Tree<E> {
+	/// check the levels and names of this tree
+	///
+	/// Returns every problem found; an empty list means the tree
+	/// is well formed. This is an O(n) operation meant for tests
+	/// and debugging.
+	pub fn validate(&self) -> Vec<Invalid> {
+		let mut errors = Vec::new();
+		self.validate_into(&mut HashSet::new(), &mut errors);
+		errors
+	}
+
+	/// validation that shares name tracking with other trees,
+	/// used to validate the pieces of a cursor together
+	#[doc(hidden)]
+	pub fn validate_into(&self, names: &mut HashSet<Name>, errors: &mut Vec<Invalid>) {
+		if let Some(ref n) = self.name {
+			if !names.insert(n.clone()) { errors.push(Invalid::DuplicateName(n.clone())) }
+		}
+		let TreeNode{l_branch, r_branch, ..} = force(&self.link);
+		if let Some(ref t) = l_branch {
+			errors.extend(check_level(self.level, t.level, true));
+			t.validate_into(names, errors);
+		}
+		if let Some(ref t) = r_branch {
+			errors.extend(check_level(self.level, t.level, false));
+			t.validate_into(names, errors);
+		}
+	}
+}
+
 impl<E: Debug+Clone+Eq+Hash+'static> Clone for Tree<E> {
 	fn clone(&self) -> Self {
 		Tree{level: self.level, name: self.name.clone(), link: self.link.clone()}
@@ -328,6 +391,28 @@ mod tests {
 
 		assert_eq!(27, sum);
 	}
+
+  #[test]
+  fn test_validate() {
+		let good = 
+		Tree::new(3, Some(name_of_usize(3)),(),
+			Tree::new(2, Some(name_of_usize(2)),(),None,None),
+			Tree::new(3, Some(name_of_usize(4)),(),None,None),
+		).unwrap();
+		assert!(good_levels(&good));
+		assert_eq!(Vec::<Invalid>::new(), good.validate());
+
+		let bad = 
+		Tree::new(3, Some(name_of_usize(3)),(),
+			Tree::new(3, Some(name_of_usize(2)),(),None,None),
+			Tree::new(1, Some(name_of_usize(3)),(),None,None),
+		).unwrap();
+		assert!(!good_levels(&bad));
+		assert_eq!(vec![
+			Invalid::Level{parent: 3, branch: 3, left: true},
+			Invalid::DuplicateName(name_of_usize(3)),
+		], bad.validate());
+	}
 }
 
 
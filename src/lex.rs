@@ -0,0 +1,304 @@
+//! Incremental Lexing
+//!
+//! Turns a sequence of characters into a sequence of tokens
+//!
+//! - tokens are described by a deterministic automaton, `Dfa`
+//! - each token is the longest run of characters the automaton
+//!   accepts, without backtracking
+//! - tokens may span any number of subsequences
+//!
+//! `lex` folds the characters left to right, keeping the automaton
+//! state and the text of the unfinished token along with the tokens
+//! so far. Tokens are archived at each named point of the input, so
+//! after an edit the fold is memoized again as soon as it reaches
+//! a point with the same state as before, usually at the first
+//! token boundary after the edit.
+
+use std::rc::Rc;
+use std::fmt::Debug;
+use std::hash::Hash;
+
+use archive_stack::{AStack,AtTail};
+use raz::RazTree;
+use raz_meta::RazMeta;
+use memo::MemoFrom;
+use adapton::engine::*;
+
+/// The start state of every `Dfa`
+pub const START: usize = 0;
+
+type CharClass = Rc<dyn Fn(char) -> bool>;
+type Emit<T> = Rc<dyn Fn(&str) -> T>;
+
+enum Accept<T> {
+	Token(Emit<T>),
+	Skip,
+}
+
+/// Deterministic automaton describing tokens
+///
+/// States are numbered from `START`. Edges are tried in the order they
+/// were added. Text ending in an accepting state becomes a token,
+/// or is dropped for states marked with `skip`. Characters that
+/// don't continue a token and can't start one are dropped.
+pub struct Dfa<T> {
+	edges: Vec<Vec<(CharClass,usize)>>,
+	accepts: Vec<Option<Accept<T>>>,
+}
+
+/// Progress through a token, between characters
+#[derive(Clone,Debug,PartialEq,Eq,Hash)]
+pub struct LexState {
+	state: usize,
+	lexeme: String,
+}
+
+impl LexState {
+	/// the state before any characters
+	pub fn start() -> Self {
+		LexState{state: START, lexeme: String::new()}
+	}
+}
+
+impl<T> Dfa<T> {
+	/// new automaton with only a start state
+	pub fn new() -> Self {
+		Dfa{edges: vec![Vec::new()], accepts: vec![None]}
+	}
+
+	/// adds a state, returning its index
+	pub fn add_state(&mut self) -> usize {
+		self.edges.push(Vec::new());
+		self.accepts.push(None);
+		self.edges.len() - 1
+	}
+
+	/// adds an edge for the characters in `class`
+	pub fn edge<F>(&mut self, from: usize, to: usize, class: F) where
+		F: 'static + Fn(char) -> bool
+	{
+		assert!(to < self.edges.len(), "edge to missing state {}", to);
+		self.edges[from].push((Rc::new(class), to));
+	}
+
+	/// marks a state as accepting, making tokens of its text with `token`
+	pub fn accept<F>(&mut self, state: usize, token: F) where
+		F: 'static + Fn(&str) -> T
+	{
+		self.accepts[state] = Some(Accept::Token(Rc::new(token)));
+	}
+
+	/// marks a state as accepting text that makes no token,
+	/// like whitespace
+	pub fn skip(&mut self, state: usize) {
+		self.accepts[state] = Some(Accept::Skip);
+	}
+
+	fn next(&self, state: usize, c: char) -> Option<usize> {
+		self.edges[state].iter().find(|edge| (edge.0)(c)).map(|edge| edge.1)
+	}
+
+	fn emit(&self, lex: &LexState) -> Option<T> {
+		if lex.lexeme.is_empty() { return None }
+		match self.accepts[lex.state] {
+			Some(Accept::Token(ref token)) => Some(token(&lex.lexeme)),
+			_ => None,
+		}
+	}
+
+	/// moves past one character, returning the token it ends, if any
+	pub fn step(&self, mut lex: LexState, c: char) -> (LexState, Option<T>) {
+		if let Some(to) = self.next(lex.state, c) {
+			lex.state = to;
+			lex.lexeme.push(c);
+			return (lex, None)
+		}
+		let token = self.emit(&lex);
+		lex.lexeme.clear();
+		match self.next(START, c) {
+			Some(to) => {
+				lex.state = to;
+				lex.lexeme.push(c);
+			},
+			None => lex.state = START,
+		}
+		(lex, token)
+	}
+
+	/// the last token, after all the characters
+	pub fn finish(&self, lex: LexState) -> Option<T> {
+		self.emit(&lex)
+	}
+
+	/// Non-incremental tokens of a string
+	pub fn tokenize(&self, text: &str) -> Vec<T> {
+		let mut tokens = Vec::new();
+		let lex = text.chars().fold(LexState::start(), |lex, c| {
+			let (lex, token) = self.step(lex, c);
+			tokens.extend(token);
+			lex
+		});
+		tokens.extend(self.finish(lex));
+		tokens
+	}
+}
+
+impl<T> Default for Dfa<T> {
+	fn default() -> Self { Self::new() }
+}
+
+/// Incremental tokens of a sequence of characters
+///
+/// Tokens keep the levels of the input, and are archived under its
+/// names paired with "lex".
+pub fn lex<C,T,M,N>(chars: RazTree<C,M>, dfa: Rc<Dfa<T>>) -> RazTree<T,N> where
+	C: 'static + Debug+Clone+Eq+Hash+Into<char>,
+	T: 'static + Debug+Clone+Eq+Hash,
+	M: RazMeta<C>,
+	N: RazMeta<T>,
+{
+	let nm_lex = name_of_string(String::from("lex"));
+	ns(nm_lex.clone(), || {
+		let step_dfa = dfa.clone();
+		let (mut tokens, lex) = chars.fold_lr_meta(
+			(AStack::new(), LexState::start()),
+			Rc::new(move |(mut tokens, lex): (AStack<T,u32>,LexState), c: &C| {
+				let (lex, token) = step_dfa.step(lex, c.clone().into());
+				if let Some(token) = token { tokens.push(token) }
+				(tokens, lex)
+			}),
+			Rc::new(move |(mut tokens, lex): (AStack<T,u32>,LexState), (lev, n): (u32,Option<Name>)| {
+				tokens.archive(n.map(|n| name_pair(n, nm_lex.clone())), lev);
+				(tokens, lex)
+			}),
+		);
+		if let Some(token) = dfa.finish(lex) { tokens.push(token) }
+		// the tree reuses the names of the archive, in its own namespace
+		ns(name_of_string(String::from("to_tree")), || RazTree::memo_from(&AtTail(tokens)))
+	})
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use raz::Raz;
+	use raz_meta::Count;
+
+	#[derive(Clone,Debug,PartialEq,Eq,Hash)]
+	enum Tok {
+		Num(u32),
+		Word(String),
+		Plus,
+	}
+
+	fn example_dfa() -> Dfa<Tok> {
+		let mut dfa = Dfa::new();
+		let num = dfa.add_state();
+		let word = dfa.add_state();
+		let plus = dfa.add_state();
+		let space = dfa.add_state();
+		dfa.edge(START, num, |c| c.is_ascii_digit());
+		dfa.edge(num, num, |c| c.is_ascii_digit());
+		dfa.edge(START, word, |c| c.is_alphabetic());
+		dfa.edge(word, word, |c| c.is_alphanumeric());
+		dfa.edge(START, plus, |c| c == '+');
+		dfa.edge(START, space, |c| c == ' ');
+		dfa.edge(space, space, |c| c == ' ');
+		dfa.accept(num, |s| Tok::Num(s.parse().unwrap()));
+		dfa.accept(word, |s| Tok::Word(s.to_string()));
+		dfa.accept(plus, |_| Tok::Plus);
+		dfa.skip(space);
+		dfa
+	}
+
+	fn contents(t: RazTree<Tok,Count>) -> Vec<Tok> {
+		t.fold_lr(Vec::new(), Rc::new(|mut v: Vec<Tok>, e: &Tok| { v.push(e.clone()); v }))
+	}
+
+	#[test]
+	fn test_tokenize() {
+		let dfa = example_dfa();
+		assert_eq!(
+			vec![Tok::Num(12), Tok::Plus, Tok::Word(String::from("ab3")), Tok::Num(4), Tok::Plus],
+			dfa.tokenize("12 +ab3  4?+"),
+		);
+		assert!(dfa.tokenize("").is_empty());
+	}
+
+	#[test]
+	fn test_lex() {
+		let dfa = Rc::new(example_dfa());
+		let text = "x1 + 23 + 456 + 7 ++ 8910 + abc def + 1234567";
+		let mut r: Raz<char,Count> = Raz::new();
+		for (i,c) in text.chars().enumerate() {
+			r.push_left(c);
+			// split up tokens between subsequences
+			if i % 3 == 1 { r.archive_left(::inc_level(),Some(name_of_usize(i))) }
+		}
+		let chars = r.unfocus();
+		let tokens = ns(name_of_string(String::from("first")),||{ lex(chars.clone(), dfa.clone()) });
+		assert!(tokens.validate().is_empty());
+		assert_eq!(dfa.tokenize(text), contents(tokens));
+
+		// after an edit in the middle of a token
+		let mut r = chars.focus(11usize).unwrap();
+		r.push_left('0');
+		r.push_left(' ');
+		let tokens = ns(name_of_string(String::from("edit")),||{ lex(r.unfocus(), dfa.clone()) });
+		assert_eq!(dfa.tokenize("x1 + 23 + 40 56 + 7 ++ 8910 + abc def + 1234567"), contents(tokens));
+
+		let empty: RazTree<Tok,Count> = lex(RazTree::<char,Count>::empty(), dfa);
+		assert!(empty.is_empty());
+	}
+
+	// naive arts recompute everything
+	#[cfg(not(feature = "naive"))]
+	#[test]
+	fn test_lex_reuse() {
+		use std::cell::Cell;
+		use raz_meta::Names;
+		thread_local!(static STEPS: Cell<usize> = const { Cell::new(0) });
+
+		// a character that counts the times it is lexed
+		#[derive(Clone,Debug,PartialEq,Eq,Hash)]
+		struct Counted(char);
+		impl From<Counted> for char {
+			fn from(c: Counted) -> char {
+				STEPS.with(|n| n.set(n.get() + 1));
+				c.0
+			}
+		}
+
+		::adapton::engine::manage::init_dcg();
+		let dfa = Rc::new(example_dfa());
+		let text = (0..100).map(|i| format!("w{} + {} ", i, i * 7)).collect::<String>();
+		let mut r: Raz<Counted,Count> = Raz::new();
+		for (i,c) in text.chars().enumerate() {
+			r.push_left(Counted(c));
+			if i % 16 == 15 { r.archive_left(::inc_level(),Some(name_of_usize(i))) }
+		}
+		let chars = r.unfocus();
+		let steps = || STEPS.with(|n| n.replace(0));
+		steps();
+		let tokens: RazTree<Tok,Names> = lex(chars.clone(), dfa.clone());
+		assert_eq!(text.len(), steps());
+		let names = tokens.meta().names();
+		assert!(!names.is_empty());
+
+		// a space in the middle of a word, in one leaf
+		let mut r = chars.focus(text.len() / 2 + 1).unwrap();
+		r.push_left(Counted(' '));
+		let edited = r.unfocus();
+		let mut edited_text = text.clone();
+		edited_text.insert(text.len() / 2 + 1, ' ');
+		steps();
+		let tokens: RazTree<Tok,Names> = lex(edited, dfa.clone());
+		let relexed = steps();
+		assert!(relexed < 64, "{} of {} characters lexed again", relexed, text.len());
+		// the tokens keep the archive names of the first run
+		assert_eq!(names.len(), tokens.meta().len());
+		assert!(names.iter().all(|n| tokens.meta().contains(n)));
+		let toks = tokens.fold_lr(Vec::new(), Rc::new(|mut v: Vec<Tok>, e: &Tok| { v.push(e.clone()); v }));
+		assert_eq!(dfa.tokenize(&edited_text), toks);
+	}
+}
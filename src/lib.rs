@@ -22,25 +22,51 @@
 //! within a namespace (`adapton::engine::ns`) should produce a version
 //! whose edits do not affect the original, but this has not been
 //! thoroughly tested.
+//!
+//! Build with the `naive` feature to replace the incremental
+//! articulations with plain reference counted nodes. The API is
+//! unchanged, but folds and maps recompute from scratch, without the
+//! overhead of the incremental engine.
 
 extern crate rand;
-#[macro_use] extern crate adapton;
+#[cfg_attr(not(feature = "naive"), macro_use)]
+extern crate adapton;
+
+// without the engine, memoized calls are plain function calls
+#[cfg(feature = "naive")]
+macro_rules! memo {
+	( $nm:expr =>> $fun:expr , $( $lab:ident : $arg:expr ),* ) => {{
+		let _ = $nm;
+		$fun( $( $arg ),* )
+	}};
+	( $nm:expr =>> $fun:expr , $( $lab1:ident : $arg1:expr ),* ;; $( $lab2:ident : $arg2:expr ),* ) => {{
+		let _ = $nm;
+		$fun( $( $arg1 ),* , $( $arg2 ),* )
+	}};
+}
 
+use std::hash::{Hash,Hasher};
+
+mod art;                // Adapton articulations, or plain nodes with the `naive` feature
 #[doc(hidden)]
 pub mod trees;          // old work, but want to reincorporate the Level trait into current Raz
 pub mod memo;           // Conversion function traits
 pub mod stack;          // Cons-list
 pub mod archive_stack;  // Sequences with subsequence vectors and metadata
+pub mod chunker;        // Content-defined archive points, from a rolling hash
 pub mod level_tree;     // generic tree with cannonical structure, basis for incremental functions
 pub mod tree_cursor;    // interface for traversing a level tree
 pub mod raz;            // Gauged Incremental Random Access Zipper
 pub mod raz_meta;       // Generic interface and concrete versions of metadata for searching the Raz
+pub mod frozen;         // Immutable, thread-safe snapshots of sequences
+pub mod trie;           // Hash tries for finite sets and maps, with incremental unions
+pub mod rope;           // Strings of shared pieces, for incremental string building
+pub mod lex;            // Incremental tokenizing of character sequences
+pub mod expr;           // Incremental evaluation of operator-precedence expressions
 
-// Two forms of tries. They work, but performance needs improvement
+// Another form of finite map. It works, but performance needs improvement
 #[doc(hidden)]
 pub mod skiplist;
-#[doc(hidden)]
-pub mod trie;
 
 /// Gauged Incremental Raz with element counts
 pub type IRaz<E> = raz::Raz<E,raz_meta::Count>;
@@ -55,3 +81,72 @@ pub type ArchiveStack<E> = archive_stack::AStack<E,()>;
 pub fn inc_level() -> u32 {
   level_tree::gen_branch_level(&mut rand::thread_rng())
 }
+
+///level generator derived from a hash of some content
+///
+/// Levels have the same distribution as `inc_level()`, but equal
+/// content always gets the same level, so structures built from the
+/// same data have the same shape on every run. The hash is FNV-1a,
+/// with integers fed as fixed-width little-endian bytes, and mixed
+/// by murmur3's 64-bit finalizer so that its low bits are uniform.
+/// It is the same across Rust releases and platforms, for content
+/// whose `Hash` implementation doesn't change. Hash chunk contents
+/// for content-defined boundaries, or positions for fixed ones.
+pub fn hash_level<T: Hash>(content: &T) -> u32 {
+  (hash64(content) << 1).trailing_zeros()
+}
+
+///name generator derived from a hash of some content
+///
+/// Equal content gets equal names, so callers must pair these
+/// with something else when the same content may appear twice.
+pub fn hash_name<T: Hash>(content: &T) -> adapton::engine::Name {
+  adapton::engine::name_of_hash64(hash64(&(NAME_SALT, content)))
+}
+
+// keeps names independent from levels of the same content
+const NAME_SALT: u64 = 0x6e61_6d65;
+
+// FNV-1a over the data fed by `Hash`, the same on every run,
+// release and platform. Only the `Hash` implementations of the
+// content could change it. The low bits of FNV-1a depend only on
+// the low bits of each byte, so the result is mixed with murmur3's
+// finalizer before callers read its trailing zeros or remainders.
+fn hash64<T: Hash>(content: &T) -> u64 {
+  let mut hasher = StableHasher(FNV_OFFSET);
+  content.hash(&mut hasher);
+  fmix64(hasher.finish())
+}
+
+fn fmix64(mut k: u64) -> u64 {
+  k ^= k >> 33;
+  k = k.wrapping_mul(0xff51_afd7_ed55_8ccd);
+  k ^= k >> 33;
+  k = k.wrapping_mul(0xc4ce_b9fe_1a85_ec53);
+  k ^ (k >> 33)
+}
+
+const FNV_OFFSET: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64 = 0x0000_0100_0000_01b3;
+
+// writes integers as little-endian bytes, and sizes as 64 bits
+struct StableHasher(u64);
+
+impl Hasher for StableHasher {
+  fn finish(&self) -> u64 { self.0 }
+  fn write(&mut self, bytes: &[u8]) {
+    for &b in bytes {
+      self.0 = (self.0 ^ b as u64).wrapping_mul(FNV_PRIME);
+    }
+  }
+  fn write_u16(&mut self, i: u16) { self.write(&i.to_le_bytes()) }
+  fn write_u32(&mut self, i: u32) { self.write(&i.to_le_bytes()) }
+  fn write_u64(&mut self, i: u64) { self.write(&i.to_le_bytes()) }
+  fn write_u128(&mut self, i: u128) { self.write(&i.to_le_bytes()) }
+  fn write_usize(&mut self, i: usize) { self.write_u64(i as u64) }
+  fn write_i16(&mut self, i: i16) { self.write_u16(i as u16) }
+  fn write_i32(&mut self, i: i32) { self.write_u32(i as u32) }
+  fn write_i64(&mut self, i: i64) { self.write_u64(i as u64) }
+  fn write_i128(&mut self, i: i128) { self.write_u128(i as u128) }
+  fn write_isize(&mut self, i: isize) { self.write_u64(i as i64 as u64) }
+}
@@ -23,19 +23,34 @@
 //! crate-level function `inc_level`. Names must be unique, and
 //! can be generated with `adapton::engine::*`'s `name_of_usize(num)`,
 //! by passing a number from a counter.
+//! For the same tree shape on every run, `archive_left_hashed`
+//! derives the level from the archived data instead, as does
+//! `RazTree::from_iter_hashed`.
 
 use std::rc::Rc;
+use std::mem;
 
-use std::fmt::Debug;
-use std::hash::Hash;
+use std::fmt::{Debug,Display};
+use std::hash::{Hash,Hasher};
+use std::cmp::Ordering;
+use std::ops::Range;
+use std::collections::HashMap;
+use std::iter::FromIterator;
+use std::collections::hash_map::DefaultHasher;
 
-use level_tree::{Tree};
+use level_tree::{Tree,Invalid};
 use tree_cursor as tree;
 use tree_cursor::TreeUpdate;
 use archive_stack as stack;
-use raz_meta::{RazMeta,Navigation,FirstLast};
+use archive_stack::Chunk;
+use chunker::Chunker;
+use raz_meta::{RazMeta,Navigation,FirstLast,Count,CountMax,Names};
 use memo::{MemoFrom};
+use frozen::{self,FrozenSeq};
+use trie::{Trie,FinSet};
+use rope::Rope;
 
+#[cfg(not(feature = "naive"))]
 use adapton::macros::*;
 use adapton::engine::*;
 
@@ -48,9 +63,15 @@ pub struct Raz<E:Debug+Clone+Eq+Hash+'static, M:RazMeta<E>+'static> {
 	l_stack: stack::AStack<E,u32>,
 	r_stack: stack::AStack<E,u32>,
 	r_forest: tree::Cursor<TreeData<E,M>>,
+	// archive points of leaves opened by popping, after the given
+	// number of elements of the active stack, with tree names
+	l_open: Vec<(usize,u32,Option<Name>)>,
+	r_open: Vec<(usize,u32,Option<Name>)>,
 }
 
 const DEFAULT_SECTION_CAPACITY: usize = 500;
+/// elements per leaf of trees collected from iterators
+const DEFAULT_GAUGE: usize = 100;
 
 /// The data stored in the tree structure of the RAZ.
 #[derive(PartialEq,Eq,Debug,Hash,Clone)]
@@ -90,6 +111,141 @@ pub struct RazTree<E:'static+Debug+Clone+Eq+Hash, M:RazMeta<E>+'static>{
 	tree: Option<tree::Tree<TreeData<E,M>>>
 }
 
+/// Summary of the shape of a tree, from `RazTree::stats`
+#[derive(Clone,PartialEq,Eq,Debug,Default)]
+pub struct TreeStats {
+	/// number of leaves
+	pub leaves: usize,
+	/// number of elements
+	pub elements: usize,
+	/// elements in the smallest leaf
+	pub min_leaf: usize,
+	/// elements in the largest leaf
+	pub max_leaf: usize,
+	/// nodes on the longest path from the root to a leaf
+	pub depth: usize,
+	/// depth of a balanced tree with the same number of leaves
+	pub balanced_depth: usize,
+	/// number of leaves under each number of branches, from 0
+	pub leaf_depths: Vec<usize>,
+	/// branches with names
+	pub named: usize,
+	/// branches without names
+	pub unnamed: usize,
+	/// rough size of the nodes and elements in bytes, not counting
+	/// names or the incremental engine's own tables
+	pub bytes: usize,
+}
+
+impl TreeStats {
+	/// average elements per leaf
+	pub fn avg_leaf(&self) -> f64 {
+		if self.leaves == 0 { 0.0 } else { self.elements as f64 / self.leaves as f64 }
+	}
+}
+
+/// leaves and separators of a tree, left to right
+///
+/// This avoids recursion, since a tree with poor levels may be deep.
+fn tree_parts<E,M>(t: &tree::Tree<TreeData<E,M>>) -> TreeParts<E,M> where
+	E:Debug+Clone+Eq+Hash+'static,
+	M:RazMeta<E>
+{
+	let (mut leaves, mut seps) = (Vec::new(), Vec::new());
+	// subtrees, or separators between them, still to visit
+	let mut todo = vec![Ok(t.clone())];
+	while let Some(next) = todo.pop() {
+		match next {
+			Err(sep) => seps.push(sep),
+			Ok(t) => match (t.l_tree(), t.r_tree()) {
+				(None, None) => leaves.push(t),
+				(Some(l), Some(r)) => {
+					todo.push(Ok(r));
+					todo.push(Err((t.level(), t.name())));
+					todo.push(Ok(l));
+				},
+				(Some(b), None) | (None, Some(b)) => todo.push(Ok(b)),
+			},
+		}
+	}
+	(leaves, seps)
+}
+
+/// nodes on the longest path from the root to a leaf
+fn tree_depth<E,M>(t: &tree::Tree<TreeData<E,M>>) -> usize where
+	E:Debug+Clone+Eq+Hash+'static,
+	M:RazMeta<E>
+{
+	let mut depth = 0;
+	let mut todo = vec![(t.clone(), 1)];
+	while let Some((t,d)) = todo.pop() {
+		depth = ::std::cmp::max(depth, d);
+		if let Some(l) = t.l_tree() { todo.push((l, d+1)) }
+		if let Some(r) = t.r_tree() { todo.push((r, d+1)) }
+	}
+	depth
+}
+
+/// checks the data of each node, used by `RazTree::validate`
+fn validate_data<E,M>(t: &tree::Tree<TreeData<E,M>>, errors: &mut Vec<Invalid>) where
+	E:Debug+Clone+Eq+Hash+'static,
+	M:RazMeta<E>
+{
+	let (l,r) = (t.l_tree(), t.r_tree());
+	match t.peek() {
+		TreeData::Dummy => errors.push(Invalid::Dummy),
+		TreeData::Leaf(ref vec) => if vec.is_empty() { errors.push(Invalid::EmptyLeaf) },
+		data => {
+			let (lp,rp) = (l.as_ref().map(|t|t.peek()), r.as_ref().map(|t|t.peek()));
+			// dummy branches are reported on their own
+			if lp != Some(TreeData::Dummy) && rp != Some(TreeData::Dummy) {
+				let meta = TreeData::rebuild(lp.as_ref(), &data, t.level(), t.name(), rp.as_ref());
+				if meta != data { errors.push(Invalid::Meta{level: t.level(), name: t.name()}) }
+			}
+		},
+	}
+	if let Some(ref t) = l { validate_data(t, errors) }
+	if let Some(ref t) = r { validate_data(t, errors) }
+}
+
+fn freeze_tree<E,M>(t: &tree::Tree<TreeData<E,M>>) -> FrozenSeq<E> where
+	E:Debug+Clone+Eq+Hash+'static,
+	M:RazMeta<E>
+{
+	match t.peek() {
+		TreeData::Leaf(ref vec) => FrozenSeq::leaf((**vec).clone()),
+		_ => FrozenSeq::branch(
+			t.l_tree().map_or(FrozenSeq::empty(), |t| freeze_tree(&t)),
+			t.level(), t.name().is_some(),
+			t.r_tree().map_or(FrozenSeq::empty(), |t| freeze_tree(&t)),
+		),
+	}
+}
+
+fn thaw_node<E,M>(node: &frozen::Node<E>, name: &Option<Name>, count: &mut usize) -> Option<tree::Tree<TreeData<E,M>>> where
+	E:Debug+Clone+Eq+Hash+'static,
+	M:RazMeta<E>
+{
+	match *node {
+		frozen::Node::Leaf(ref vec) => {
+			if vec.is_empty() { None } else { Some(leaf(vec.clone(), None)) }
+		},
+		frozen::Node::Branch{level, named, ref left, ref right, ..} => {
+			let l = left.as_ref().and_then(|n| thaw_node(n, name, count));
+			let nm = if named { name.clone().map(|n| name_pair(n, name_of_usize(*count))) } else { None };
+			*count += 1;
+			let r = right.as_ref().and_then(|n| thaw_node(n, name, count));
+			if l.is_none() && r.is_none() { return None }
+			let data = TreeData::rebuild(
+				l.as_ref().map(|t| t.peek()).as_ref(),
+				&TreeData::Dummy, level, nm.clone(),
+				r.as_ref().map(|t| t.peek()).as_ref(),
+			);
+			tree::Tree::new(level, nm, data, l, r)
+		},
+	}
+}
+
 fn treetop_meta<E,M>(t: Option<&tree::Tree<TreeData<E,M>>>) -> M where
 	E:Debug+Clone+Eq+Hash+'static,
 	M:RazMeta<E>
@@ -114,6 +270,100 @@ impl<E: Debug+Clone+Eq+Hash+'static, M:RazMeta<E>> RazTree<E,M> {
 		RazTree{meta: treetop_meta(None), tree: None}
 	}
 
+	/// make an immutable, thread-safe copy of the sequence
+	///
+	/// This copies every element. The levels of the tree are kept,
+	/// along with which branches were named.
+	pub fn freeze(&self) -> FrozenSeq<E> {
+		self.tree.as_ref().map_or(FrozenSeq::empty(), |t| freeze_tree(t))
+	}
+
+	/// import a frozen sequence as an incremental tree
+	///
+	/// Branches that were named are named again by pairing `name`
+	/// with their position among all branches, left to right. With
+	/// no `name` the tree is unnamed.
+	pub fn thaw(seq: &FrozenSeq<E>, name: Option<Name>) -> Self {
+		let mut count = 0;
+		let tree = seq.0.as_ref().and_then(|n| thaw_node(n, &name, &mut count));
+		RazTree{meta: treetop_meta(tree.as_ref()), tree}
+	}
+
+	/// check the structure of the tree
+	///
+	/// Along with levels and names (see `Tree::validate`), checks
+	/// that there are no empty leaves or leftover `Dummy` data, and
+	/// that the meta data of each branch matches a recomputation from
+	/// its children. A mismatch in the meta data of the whole tree is
+	/// reported with level 0 and no name. Returns every problem found.
+	/// This is an O(n) operation meant for tests and debugging.
+	pub fn validate(&self) -> Vec<Invalid> {
+		let mut errors = Vec::new();
+		if let Some(ref t) = self.tree {
+			errors = t.validate();
+			validate_data(t, &mut errors);
+			if t.peek() == TreeData::Dummy { return errors }
+		}
+		if self.meta != treetop_meta(self.tree.as_ref()) {
+			errors.push(Invalid::Meta{level: 0, name: None});
+		}
+		errors
+	}
+
+	/// the number of nodes on the longest path from the root to a
+	/// leaf, 0 for an empty tree
+	pub fn depth(&self) -> usize {
+		self.tree.as_ref().map_or(0, |t| tree_depth(t))
+	}
+
+	/// a summary of the shape of the tree
+	///
+	/// A tree whose `depth` is well over its `balanced_depth` has
+	/// poor levels, and may be improved with `rebalance`. This is an
+	/// O(n) operation in the number of leaves.
+	pub fn stats(&self) -> TreeStats {
+		let mut stats = TreeStats::default();
+		let mut todo = self.tree.iter().map(|t| (t.clone(), 0)).collect::<Vec<_>>();
+		while let Some((t,d)) = todo.pop() {
+			if let TreeData::Leaf(ref vec) = t.peek() {
+				stats.min_leaf = if stats.leaves == 0 { vec.len() } else { ::std::cmp::min(stats.min_leaf, vec.len()) };
+				stats.max_leaf = ::std::cmp::max(stats.max_leaf, vec.len());
+				stats.leaves += 1;
+				stats.elements += vec.len();
+				if stats.leaf_depths.len() <= d { stats.leaf_depths.resize(d + 1, 0) }
+				stats.leaf_depths[d] += 1;
+			} else if t.name().is_some() { stats.named += 1 } else { stats.unnamed += 1 }
+			if let Some(l) = t.l_tree() { todo.push((l, d+1)) }
+			if let Some(r) = t.r_tree() { todo.push((r, d+1)) }
+		}
+		if stats.leaves == 0 { return stats }
+		stats.depth = stats.leaf_depths.len();
+		stats.balanced_depth = (stats.leaves as f64).log2().ceil() as usize + 1;
+		let nodes = stats.leaves + stats.named + stats.unnamed;
+		stats.bytes = stats.elements * mem::size_of::<E>()
+			+ stats.leaves * mem::size_of::<Vec<E>>()
+			+ nodes * (mem::size_of::<TreeData<E,M>>() + 2 * mem::size_of::<Option<tree::Tree<TreeData<E,M>>>>());
+		stats
+	}
+
+	/// Rebuilds the tree with new levels, so that it is balanced
+	///
+	/// The leaves are kept, and the separators keep their order and
+	/// names, but are given levels of a complete binary tree, so the
+	/// depth is logarithmic in the number of leaves. Use this on trees
+	/// built with poor levels, as by `join` or `from_vec`. Since the
+	/// names are reused with new levels, memoized results for the old
+	/// tree are replaced rather than reused.
+	pub fn rebalance(self) -> Self {
+		let t = match self.tree { None => return self, Some(t) => t };
+		let (leaves, seps) = tree_parts(&t);
+		let mut names = seps.into_iter().map(|(_,n)| n);
+		RazTree::from_nodes(leaves.into_iter().enumerate().map(|(i,l)| {
+			// the levels of a complete tree, counting from 1 above the leaves
+			(l, (i + 1).trailing_zeros() + 1, names.next().and_then(|n| n))
+		}))
+	}
+
 	/// Combine two trees left to right
 	///
 	/// returns None if either tree is empty.
@@ -140,6 +390,106 @@ impl<E: Debug+Clone+Eq+Hash+'static, M:RazMeta<E>> RazTree<E,M> {
 		Some(RazTree{meta: treetop_meta(Some(&tree)), tree: Some(tree)})
 	}
 
+	/// Make a RazTree from a sequence, with `gauge` elements per leaf
+	///
+	/// Leaves are separated by random levels, as from `inc_level()`,
+	/// so each build has a different shape. Only the level convention
+	/// is shared with trees built by `push_left` and `archive_left`.
+	/// If a name is given, each separator is named by pairing it with
+	/// the separator's position, counting from 0. These names are
+	/// positional: inserting data shifts every later name, so memoized
+	/// results for the rest of the tree are not reused. Use
+	/// `from_iter_hashed` or `from_iter_chunked` for names that
+	/// survive edits. The tree is built bottom-up in O(n).
+	pub fn from_iter_gauged<I>(iter: I, gauge: usize, name_base: Option<Name>) -> Self where
+		I: IntoIterator<Item=E>
+	{
+		assert!(gauge > 0, "gauge must be positive");
+		let mut iter = iter.into_iter();
+		let chunks = (0..).map(|_| iter.by_ref().take(gauge).collect::<Vec<_>>()).take_while(|v| !v.is_empty());
+		RazTree::from_leaves(chunks.enumerate().map(|(i,vec)| {
+			(vec, ::inc_level(), name_base.clone().map(|n| name_pair(n, name_of_usize(i))))
+		}))
+	}
+
+	/// Builds a tree from an iterator, with boundaries, levels and
+	/// names derived from the data
+	///
+	/// A leaf ends after an element whose hash is a multiple of
+	/// `gauge`, or when it grows to four times the gauge, so leaves
+	/// average about `gauge` elements. This is a `Chunker` with a
+	/// window of one element, and the separators get its levels and
+	/// names, from the hash of the element that ends each leaf. Names
+	/// pair that hash with the number of earlier leaves ending with an
+	/// equal element. Building from the same data always gives the
+	/// same tree, and an edit only moves the boundaries near it.
+	pub fn from_iter_hashed<I>(iter: I, gauge: usize, name_base: Option<Name>) -> Self where
+		I: IntoIterator<Item=E>
+	{
+		RazTree::from_iter_chunked(iter, &mut Chunker::with_window(gauge, 1, name_base))
+	}
+
+	/// Builds a tree from an iterator, archiving where the chunker
+	/// finds boundaries
+	///
+	/// The tree is the same as one built by `push_left_chunked` with
+	/// a chunker in the same state.
+	pub fn from_iter_chunked<I>(iter: I, chunker: &mut Chunker) -> Self where
+		I: IntoIterator<Item=E>
+	{
+		let mut iter = iter.into_iter();
+		let chunks = (0..).map(|_| {
+			let mut vec = Vec::new();
+			for e in iter.by_ref() {
+				let sep = chunker.push(&e);
+				vec.push(e);
+				if let Some((level,name)) = sep { return (vec, level, name) }
+			}
+			(vec, 0, None)
+		}).take_while(|c| !c.0.is_empty());
+		RazTree::from_leaves(chunks)
+	}
+
+	// builds a tree bottom-up from leaf data, each with the level and
+	// name of the separator after it (the last one is unused). Names
+	// are paired with "tree", as when archives become tree nodes.
+	fn from_leaves<I>(leaves: I) -> Self where
+		I: Iterator<Item=(Vec<E>,u32,Option<Name>)>
+	{
+		let nmtree = name_of_string(String::from("tree"));
+		RazTree::from_nodes(leaves.map(|(vec,lev,n)| {
+			(leaf(vec, None), lev, n.map(|n| name_pair(n, nmtree.clone())))
+		}))
+	}
+
+	// builds a tree bottom-up from subtrees, each with the level and
+	// name of the separator after it (the last one is unused)
+	fn from_nodes<I>(mut nodes: I) -> Self where
+		I: Iterator<Item=(ltree::Tree<TreeData<E,M>>,u32,Option<Name>)>
+	{
+		let (mut tree, mut level, mut name) = match nodes.next() {
+			None => return RazTree::empty(),
+			Some(node) => node,
+		};
+		// nodes still waiting for their right branch, from the root down
+		let mut spine: Spine<E,M> = Vec::new();
+		for (next,next_lev,next_n) in nodes {
+			// lower nodes are complete, and go to the left of this one
+			while spine.last().is_some_and(|s| s.1 < level) {
+				let (l,lev,n) = spine.pop().unwrap();
+				tree = bin(l, lev, n, tree);
+			}
+			spine.push((tree, level, name));
+			tree = next;
+			level = next_lev;
+			name = next_n;
+		}
+		while let Some((l,lev,n)) = spine.pop() {
+			tree = bin(l, lev, n, tree);
+		}
+		RazTree{meta: treetop_meta(Some(&tree)), tree: Some(tree)}
+	}
+
 	/// Runs an incremental binary function over the sequence data
 	///
 	/// This is calculated from data in leaves of a tree structure,
@@ -317,6 +667,275 @@ impl<E: Debug+Clone+Eq+Hash+'static, M:RazMeta<E>> RazTree<E,M> {
 	}
 
 
+	/// An incremental mapping of each element to any number of
+	/// elements, returning a new tree
+	///
+	/// The levels and names of the original are used as archive points
+	/// of the result. Subtrees with no elements after mapping are dropped.
+	pub fn flat_map<R,F,N:RazMeta<R>>(self, f: Rc<F>) -> RazTree<R,N> where
+		R: 'static + Eq+Clone+Hash+Debug,
+		F: 'static + Fn(&E) -> Vec<R>,
+	{
+		let tree = self.tree.and_then(|tree| {
+			tree.fold_up_meta(Rc::new(move |l,c,lv,n,r|{
+				match c {
+					TreeData::Leaf(ref vec) => {
+						let mapped = vec.iter().flat_map(|e|f(e)).collect::<Vec<_>>();
+						if mapped.is_empty() { None } else { Some(leaf(mapped,n)) }
+					},
+					_ => { match (l,r) {
+						(None, None) => None,
+						(Some(t),None) | (None, Some(t)) => t,
+						(Some(None),Some(t)) | (Some(t),Some(None)) => t,
+						(Some(Some(lt)),Some(Some(rt))) => Some(bin(lt,lv,n,rt)),
+					}},
+				}
+			}))
+		});
+		RazTree{meta: treetop_meta(tree.as_ref()), tree}
+	}
+
+	/// The set of distinct elements in the sequence
+	///
+	/// Sets are collected from each leaf and combined with unions
+	/// memoized by the names of the tree, so only the unions along the
+	/// path to an edited leaf are redone.
+	pub fn distinct(self) -> FinSet<E> {
+		let nm_distinct = name_of_string(String::from("distinct"));
+		ns(nm_distinct.clone(), || {
+			self.tree.map_or(Trie::empty(), |tree| {
+				tree.fold_up_meta(Rc::new(move |l:Option<FinSet<E>>,c,_lv,n:Option<Name>,r:Option<FinSet<E>>|{
+					match c {
+						TreeData::Leaf(ref vec) => {
+							Trie::from_hashmap(vec.iter().map(|e:&E|(e.clone(),())).collect())
+						},
+						_ => Trie::union(
+							l.unwrap_or_else(Trie::empty),
+							r.unwrap_or_else(Trie::empty),
+							n.map(|n| name_pair(n, nm_distinct.clone())),
+						),
+					}
+				}))
+			})
+		})
+	}
+
+	/// The number of distinct elements in the sequence
+	///
+	/// See `distinct`
+	pub fn distinct_count(self) -> usize {
+		self.distinct().len()
+	}
+
+	/// The number of elements for each key
+	///
+	/// Counts are collected from each leaf and summed with unions
+	/// memoized by the names of the tree, like `distinct`.
+	pub fn histogram<K,F>(self, key: Rc<F>) -> Trie<K,usize> where
+		K: 'static + Eq+Clone+Hash+Debug,
+		F: 'static + Fn(&E) -> K,
+	{
+		let nm_hist = name_of_string(String::from("histogram"));
+		ns(nm_hist.clone(), || {
+			self.tree.map_or(Trie::empty(), |tree| {
+				tree.fold_up_meta(Rc::new(move |l:Option<Trie<K,usize>>,c,_lv,n:Option<Name>,r:Option<Trie<K,usize>>|{
+					match c {
+						TreeData::Leaf(ref vec) => {
+							let mut counts = HashMap::new();
+							for e in vec.iter() { *counts.entry(key(e)).or_insert(0) += 1 }
+							Trie::from_hashmap(counts)
+						},
+						_ => Trie::union_with(
+							l.unwrap_or_else(Trie::empty),
+							r.unwrap_or_else(Trie::empty),
+							n.map(|n| name_pair(n, nm_hist.clone())),
+							Rc::new(|_k:&K,a,b|a+b),
+						),
+					}
+				}))
+			})
+		})
+	}
+
+	/// The subsequence of elements for each key
+	///
+	/// Each subsequence keeps the order of the original and is built
+	/// with its levels, so they are cannonical trees. Branches get names
+	/// from the original's names and a hash of the key. Partial maps are
+	/// combined with unions memoized by the names of the tree.
+	pub fn group_by<K,F>(self, key: Rc<F>) -> Trie<K,RazTree<E,M>> where
+		K: 'static + Eq+Clone+Hash+Debug,
+		F: 'static + Fn(&E) -> K,
+	{
+		let nm_group = name_of_string(String::from("group_by"));
+		ns(nm_group.clone(), || {
+			self.tree.map_or(Trie::empty(), |tree| {
+				tree.fold_up_meta(Rc::new(move |l:Option<Trie<K,RazTree<E,M>>>,c,lv,n:Option<Name>,r:Option<Trie<K,RazTree<E,M>>>|{
+					match c {
+						TreeData::Leaf(ref vec) => {
+							let mut groups = HashMap::new();
+							for e in vec.iter() { groups.entry(key(e)).or_insert_with(Vec::new).push(e.clone()) }
+							Trie::from_hashmap(groups.into_iter().map(|(k,v)|(k,RazTree::from_vec(v).unwrap())).collect())
+						},
+						_ => {
+							let n = n.map(|n| name_pair(n, nm_group.clone()));
+							let join_name = n.clone();
+							Trie::union_with(
+								l.unwrap_or_else(Trie::empty),
+								r.unwrap_or_else(Trie::empty),
+								n,
+								Rc::new(move |k:&K,a,b| {
+									let name = join_name.clone().map(|n| {
+										let mut hasher = DefaultHasher::new();
+										k.hash(&mut hasher);
+										name_pair(n, name_of_hash64(hasher.finish()))
+									});
+									RazTree::join(a,lv,name,b).unwrap()
+								}),
+							)
+						},
+					}
+				}))
+			})
+		})
+	}
+
+	/// The greatest element by the given comparison, or the last
+	/// of equal elements
+	///
+	/// An incremental fold, so different comparisons should be run
+	/// in different namespaces. See `RazTree::argmax` for a position.
+	pub fn max_by<F>(self, compare: Rc<F>) -> Option<E> where
+		F: 'static + Fn(&E,&E) -> Ordering,
+	{
+		ns(name_of_string(String::from("max_by")), || {
+			self.fold_up(Rc::new(|e:&E|e.clone()), Rc::new(move |a,b|{
+				if compare(&a,&b) == Ordering::Greater { a } else { b }
+			}))
+		})
+	}
+
+	/// The least element by the given comparison, or the first
+	/// of equal elements
+	///
+	/// An incremental fold, like `max_by`.
+	pub fn min_by<F>(self, compare: Rc<F>) -> Option<E> where
+		F: 'static + Fn(&E,&E) -> Ordering,
+	{
+		ns(name_of_string(String::from("min_by")), || {
+			self.fold_up(Rc::new(|e:&E|e.clone()), Rc::new(move |a,b|{
+				if compare(&a,&b) == Ordering::Greater { b } else { a }
+			}))
+		})
+	}
+
+	/// An incremental sliding window over the sequence, returning a
+	/// new tree with `f` applied to each window of `k` elements
+	///
+	/// The result is empty if there are fewer than `k` elements. Each
+	/// subtree keeps its first and last `k-1` elements, so an edit only
+	/// recomputes the windows that overlap it along with the path to
+	/// the root. Work is memoized per window size, but not per `f`, so
+	/// calls with different functions on the same tree need their own
+	/// namespaces (`adapton::engine::ns`).
+	///
+	/// # Panics
+	/// Panics if `k` is 0
+	pub fn windows<R,F,N:RazMeta<R>>(self, k: usize, f: Rc<F>) -> RazTree<R,N> where
+		R: 'static + Eq+Clone+Hash+Debug,
+		F: 'static + Fn(&[E]) -> R,
+	{
+		assert!(k > 0, "window size of zero");
+		let nm_windows = name_pair(name_of_string(String::from("windows")), name_of_usize(k));
+		let f_leaf = f.clone();
+		let summary = ns(nm_windows.clone(), || {
+			self.fold_up_gauged(Rc::new(move |vec: &Vec<E>| {
+				let ends = ::std::cmp::min(k - 1, vec.len());
+				let out = vec.windows(k).map(|w|f_leaf(w)).collect::<Vec<_>>();
+				Windowed{
+					len: vec.len(),
+					first: vec[..ends].to_vec(),
+					last: vec[vec.len() - ends..].to_vec(),
+					out: if out.is_empty() { None } else { Some(leaf(out,None)) },
+				}
+			}), Rc::new(move |l: Windowed<E,R,N>, lev, n: Option<Name>, r: Windowed<E,R,N>| {
+				let mut cross = l.last.clone();
+				cross.extend_from_slice(&r.first);
+				let cross = cross.windows(k).map(|w|f(w)).collect::<Vec<_>>();
+				let cross = if cross.is_empty() { None } else { Some(leaf(cross,None)) };
+				let (n1,n2) = match n {
+					None => (None,None),
+					Some(n) => {
+						let (n1,n2) = name_fork(name_pair(n,nm_windows.clone()));
+						(Some(n1),Some(n2))
+					},
+				};
+				let ends = |a: &Vec<E>, b: &Vec<E>, from_end: bool| {
+					let mut v = a.clone();
+					v.extend_from_slice(b);
+					let keep = ::std::cmp::min(k - 1, v.len());
+					if from_end { v.split_off(v.len() - keep) } else { v.truncate(keep); v }
+				};
+				Windowed{
+					len: l.len + r.len,
+					first: if l.len < k - 1 { ends(&l.first, &r.first, false) } else { l.first },
+					last: if r.len < k - 1 { ends(&l.last, &r.last, true) } else { r.last },
+					out: join_opt(l.out, lev, n1, join_opt(cross, lev, n2, r.out)),
+				}
+			}))
+		});
+		let tree = summary.and_then(|s| s.out);
+		RazTree{meta: treetop_meta(tree.as_ref()), tree}
+	}
+
+	/// The elements formatted and joined by `sep`
+	///
+	/// The text of each leaf and each subtree is kept in a `Rope`, so
+	/// after an edit only the edited leaf is formatted again and the
+	/// rest of the text is shared. Work is memoized per separator, but
+	/// not per formatting of `E`, so changing how elements are
+	/// displayed needs its own namespace (`adapton::engine::ns`).
+	pub fn to_string_incremental(self, sep: &str) -> Rope where E: Display {
+		let nm_string = name_pair(name_of_string(String::from("to_string")), name_of_string(String::from(sep)));
+		let sep: Rc<str> = Rc::from(sep);
+		let leaf_sep = sep.clone();
+		ns(nm_string, || {
+			self.fold_up_gauged(Rc::new(move |vec: &Vec<E>| {
+				let strs = vec.iter().map(|e|e.to_string()).collect::<Vec<_>>();
+				Rope::from(strs.join(&*leaf_sep))
+			}), Rc::new(move |l, _lev, _n, r| {
+				Rope::concat(Rope::concat(l, Rope::Leaf(sep.clone())), r)
+			}))
+		}).unwrap_or_else(Rope::empty)
+	}
+
+	/// An incremental reversal of the sequence, returning a new tree
+	///
+	/// The result has the same levels as the original, in reverse
+	/// order, so it keeps the cannonical structure needed by later
+	/// joins and focuses. Names are paired with "reverse" and the work
+	/// is memoized for each named subtree. Reversing twice gives back
+	/// the original sequence.
+	pub fn reverse(self) -> Self {
+		let nm_rev = name_of_string(String::from("reverse"));
+		let tree = ns(nm_rev.clone(), || {
+			self.tree.map(|tree| {
+				tree.fold_up_meta(Rc::new(move |l,c,lv,n:Option<Name>,r|{
+					let n = n.map(|n| name_pair(n, nm_rev.clone()));
+					match c {
+						TreeData::Leaf(ref vec) => {
+							let mut rev = (**vec).clone();
+							rev.reverse();
+							leaf(rev, n)
+						},
+						_ => rev_join(r, lv, n, l),
+					}
+				}))
+			})
+		});
+		RazTree{meta: treetop_meta(tree.as_ref()), tree}
+	}
+
 	/// focus on a location in the sequence to begin editing.
 	pub fn focus<I:Into<M::Index>>(self, index: I) -> Option<Raz<E,M>> {
 		let mut index = index.into();
@@ -327,6 +946,8 @@ impl<E: Debug+Clone+Eq+Hash+'static, M:RazMeta<E>> RazTree<E,M> {
 					l_stack: stack::AStack::with_capacity(DEFAULT_SECTION_CAPACITY),
 					r_stack: stack::AStack::with_capacity(DEFAULT_SECTION_CAPACITY),
 					r_forest: tree::Cursor::new(),
+					l_open: Vec::new(),
+					r_open: Vec::new(),
 				})
 			},
 			RazTree{tree: Some(tree), ..} => {
@@ -350,29 +971,40 @@ impl<E: Debug+Clone+Eq+Hash+'static, M:RazMeta<E>> RazTree<E,M> {
 						Navigation::Nowhere => { return None }, 
 					}
 				}
-				// step 2: extract and copy data
-				let mut l_astack = stack::AStack::with_capacity(DEFAULT_SECTION_CAPACITY);
-				let mut r_astack = stack::AStack::with_capacity(DEFAULT_SECTION_CAPACITY);
-				let (l_cursor, tree, r_cursor) = cursor.split();
-				match tree {
-					Some(ref t) => match t.peek() {
-						TreeData::Leaf(ref vec_ref) => {
-							let (l_slice,r_slice) = M::split_vec(vec_ref, &index);
-							l_astack.extend(l_slice);
-							r_astack.extend_rev(r_slice);
-						},
-						_ => unreachable!(),
-					},
-					None => unreachable!(),
-				};
-				// step 3: integrate
-				Some(Raz{
-					l_forest: l_cursor,
-					l_stack: l_astack,
-					r_stack: r_astack,
-					r_forest: r_cursor,
-				})
+				Some(RazTree::focus_leaf(cursor, |vec| M::split_vec(vec, &index).0.len()))
+			},
+		}
+	}
+
+	// focus within the leaf at the cursor, at the position chosen by `split`
+	fn focus_leaf<F>(cursor: tree::Cursor<TreeData<E,M>>, split: F) -> Raz<E,M> where
+		F: FnOnce(&Vec<E>) -> usize
+	{
+		// step 2: extract and copy data
+		let mut l_astack = stack::AStack::with_capacity(DEFAULT_SECTION_CAPACITY);
+		let mut r_astack = stack::AStack::with_capacity(DEFAULT_SECTION_CAPACITY);
+		let (l_cursor, tree, r_cursor) = cursor.split();
+		match tree {
+			Some(ref t) => match t.peek() {
+				TreeData::Leaf(ref vec_ref) => {
+					// the stacks share the leaf's data
+					let split = split(vec_ref);
+					let chunk = Chunk::from(vec_ref.clone());
+					l_astack.push_chunk(chunk.slice(0..split));
+					r_astack.push_chunk(chunk.slice(split..chunk.len()).reversed());
+				},
+				_ => unreachable!(),
 			},
+			None => unreachable!(),
+		};
+		// step 3: integrate
+		Raz{
+			l_forest: l_cursor,
+			l_stack: l_astack,
+			r_stack: r_astack,
+			r_forest: r_cursor,
+			l_open: Vec::new(),
+			r_open: Vec::new(),
 		}
 	}
 
@@ -385,6 +1017,8 @@ impl<E: Debug+Clone+Eq+Hash+'static, M:RazMeta<E>> RazTree<E,M> {
 					l_stack: stack::AStack::with_capacity(DEFAULT_SECTION_CAPACITY),
 					r_stack: stack::AStack::with_capacity(DEFAULT_SECTION_CAPACITY),
 					r_forest: tree::Cursor::new(),
+					l_open: Vec::new(),
+					r_open: Vec::new(),
 				}
 			},
 			RazTree{tree: Some(tree), ..} => {
@@ -398,7 +1032,7 @@ impl<E: Debug+Clone+Eq+Hash+'static, M:RazMeta<E>> RazTree<E,M> {
 				match tree {
 					Some(ref t) => match t.peek() {
 						TreeData::Leaf(ref vec_ref) => {
-							r_astack.extend_rev(vec_ref);
+							r_astack.push_chunk(Chunk::from(vec_ref.clone()).reversed());
 						},
 						_ => unreachable!(),
 					},
@@ -410,6 +1044,8 @@ impl<E: Debug+Clone+Eq+Hash+'static, M:RazMeta<E>> RazTree<E,M> {
 					l_stack: l_astack,
 					r_stack: r_astack,
 					r_forest: r_cursor,
+					l_open: Vec::new(),
+					r_open: Vec::new(),
 				}
 			},
 		}
@@ -417,6 +1053,128 @@ impl<E: Debug+Clone+Eq+Hash+'static, M:RazMeta<E>> RazTree<E,M> {
 
 }
 
+impl<E: Debug+Clone+Eq+Hash+'static> RazTree<E,Count> {
+	/// An incremental element-wise combination of two sequences,
+	/// returning a new tree
+	///
+	/// The result has the structure, levels and names of `self`, and
+	/// is as long as the shorter of the two sequences. Work is memoized
+	/// for each named subtree of `self` together with the aligned part
+	/// of `other`, so edits to either one reuse the unaffected chunks.
+	pub fn zip_with<E2,R,F,N:RazMeta<R>>(self, other: RazTree<E2,Count>, f: Rc<F>) -> RazTree<R,N> where
+		E2: 'static + Eq+Clone+Hash+Debug,
+		R: 'static + Eq+Clone+Hash+Debug,
+		F: 'static + Fn(&E,&E2) -> R,
+	{
+		let tree = self.tree.and_then(|t| zip_tree(t, other.tree, f));
+		RazTree{meta: treetop_meta(tree.as_ref()), tree}
+	}
+
+	/// An incremental pairing of the elements of two sequences
+	///
+	/// See `zip_with`
+	pub fn zip<E2,N:RazMeta<(E,E2)>>(self, other: RazTree<E2,Count>) -> RazTree<(E,E2),N> where
+		E2: 'static + Eq+Clone+Hash+Debug,
+	{
+		self.zip_with(other, Rc::new(|a:&E,b:&E2|(a.clone(),b.clone())))
+	}
+}
+
+impl<E: Debug+Clone+Eq+Hash+Ord+'static> RazTree<E,CountMax<E>> {
+	/// The greatest element, read from the meta data
+	pub fn max(&self) -> Option<&E> {
+		self.meta.max.as_ref().map(|m| &m.0)
+	}
+
+	/// The index of the first greatest element, read from the meta data
+	///
+	/// Focusing on this index puts the element to the right of the cursor.
+	pub fn argmax(&self) -> Option<usize> {
+		self.meta.max.as_ref().map(|m| m.1)
+	}
+}
+
+impl<E: Debug+Clone+Eq+Hash+'static> RazTree<E,Names> {
+	/// The range of elements under the branch or leaf with this name
+	///
+	/// Names of branches are found through the meta data, and names
+	/// of leaves by checking the children of each branch on the way,
+	/// so this takes time in the depth of the tree. Returns None if
+	/// the name is not in the tree.
+	pub fn get_by_name(&self, name: &Name) -> Option<Range<usize>> {
+		self.name_path(name).map(|(_, offset, node)| {
+			let len = match node.peek() {
+				TreeData::Leaf(ref vec) => vec.len(),
+				TreeData::Branch(ref l, ref r) => l.count() + r.count(),
+				TreeData::Dummy => unreachable!(),
+			};
+			offset..offset+len
+		})
+	}
+
+	/// Focus on the position of a name
+	///
+	/// For the name of a branch this is between its left and right
+	/// subtrees, as with `focus(name)`. For the name of a leaf, this
+	/// is at the end of the leaf. Returns None if the name is not in
+	/// the tree.
+	pub fn focus_name(self, name: &Name) -> Option<Raz<E,Names>> {
+		let (path, _, _) = self.name_path(name)?;
+		let mut cursor = tree::Cursor::from(self.tree?);
+		for left in path {
+			assert!(if left { cursor.down_left() } else { cursor.down_right() });
+		}
+		if cursor.down_left() { while cursor.down_right() {} }
+		Some(RazTree::focus_leaf(cursor, |vec| vec.len()))
+	}
+
+	// the directions (true for left) from the root to the node with
+	// the name, the number of elements before it, and the node
+	fn name_path(&self, name: &Name) -> Option<NamePath<E>> {
+		let mut node = self.tree.clone()?;
+		let (mut path, mut offset) = (Vec::new(), 0);
+		loop {
+			if node.name().as_ref() == Some(name) { return Some((path, offset, node)) }
+			let (l,r) = match node.peek() { TreeData::Branch(l,r) => (l,r), _ => return None };
+			let (lt,rt) = (node.l_tree(), node.r_tree());
+			// leaf names are not in the meta data
+			let named = |t: &Option<tree::Tree<_>>| t.as_ref().is_some_and(|t| t.name().as_ref() == Some(name));
+			if l.contains(name) || named(&lt) {
+				path.push(true);
+				node = lt?;
+			} else if r.contains(name) || named(&rt) {
+				path.push(false);
+				offset += l.count();
+				node = rt?;
+			} else { return None }
+		}
+	}
+}
+
+impl<M:RazMeta<String>> RazTree<String,M> {
+	/// The strings of the sequence joined together
+	///
+	/// Like `to_string_incremental`, the text of each subtree is
+	/// kept in a `Rope` and shared after edits.
+	pub fn concat(self) -> Rope {
+		ns(name_of_string(String::from("concat")), || {
+			self.fold_up_gauged(
+				Rc::new(|vec: &Vec<String>| Rope::from(vec.concat())),
+				Rc::new(|l, _lev, _n, r| Rope::concat(l, r)),
+			)
+		}).unwrap_or_else(Rope::empty)
+	}
+}
+
+/// Collects into leaves of a default size, with random levels and
+/// no names. Use `from_iter_gauged` to give names.
+impl<E: Debug+Clone+Eq+Hash+'static, M:RazMeta<E>>
+FromIterator<E> for RazTree<E,M> {
+	fn from_iter<I: IntoIterator<Item=E>>(iter: I) -> Self {
+		RazTree::from_iter_gauged(iter, DEFAULT_GAUGE, None)
+	}
+}
+
 // impl<T: Debug+Clone+Eq+Hash+'static, M:RazMeta<E>>
 // IntoIterator for RazTree<T,M> {
 // 	type Item = T;
@@ -435,12 +1193,15 @@ Raz<E,M> {
 			l_stack: stack::AStack::with_capacity(DEFAULT_SECTION_CAPACITY),
 			r_stack: stack::AStack::with_capacity(DEFAULT_SECTION_CAPACITY),
 			r_forest: tree::Cursor::new(),
+			l_open: Vec::new(),
+			r_open: Vec::new(),
 		}
 	}
 
 	/// unfocus the RazTree before refocusing on a new location
 	/// in the sequence.
 	pub fn unfocus(mut self) -> RazTree<E,M> {
+		self.settle();
 		let nmtree = name_of_string(String::from("tree"));
 		let mut l_lev = None;
 		let mut r_lev = None;
@@ -448,49 +1209,48 @@ Raz<E,M> {
 		let mut r_nm;
 		// step 1: reconstruct local array from stack
 		l_nm = self.l_stack.name().map(|n|name_pair(n,nmtree.clone()));
-		let l_vec = if let Some((vec,lev)) = self.l_stack.next_archive() {
+		let l_vec = if let Some((vec,lev)) = self.l_stack.next_chunk() {
 			l_lev = lev;
 			if vec.len() > 0 {Some(vec)} else {None}
 		} else { None };
 		r_nm = self.r_stack.name().map(|n|name_pair(n,nmtree.clone()));
-		let r_vec = if let Some((vec,lev)) = self.r_stack.next_archive() {
+		let r_vec = if let Some((vec,lev)) = self.r_stack.next_chunk() {
 			r_lev = lev;
 			if vec.len() > 0 {Some(vec)} else {None}
 		} else { None };
+		// a leaf split by focusing is rejoined without copying
 		let vec = match (self.l_stack.is_empty(), l_vec, r_vec, self.r_stack.is_empty()) {
 			(_,Some(v),None,_) => Some(v),
-			(_,None,Some(mut v),_) => { v.reverse(); Some(v) },
-			(_,Some(mut lv),Some(mut rv),_) => {rv.reverse(); lv.extend(rv); Some(lv) },
+			(_,None,Some(v),_) => Some(v.reversed()),
+			(_,Some(lv),Some(rv),_) => Some(lv.append(rv.reversed())),
 			(false, None, None, _) => {
 				l_nm = self.l_stack.name().map(|n|name_pair(n,nmtree.clone()));
-				let (v,lev) = self.l_stack.next_archive().unwrap();
+				let (v,lev) = self.l_stack.next_chunk().unwrap();
 				l_lev = lev;
 				Some(v)
 			},
 			(true, None, None, false) => {
 				r_nm = self.r_stack.name().map(|n|name_pair(n,nmtree.clone()));
-				let (mut v,lev) = self.r_stack.next_archive().unwrap();
-				v.reverse();
+				let (v,lev) = self.r_stack.next_chunk().unwrap();
 				r_lev = lev;
-				Some(v)
+				Some(v.reversed())
 			},
 			_ => None
 		};
 		// step 2: build center tree
 		let tree = if let Some(v) = vec {
-			let mut cursor = tree::Tree::new(0,None,TreeData::Leaf(Rc::new(v)),None,None).unwrap().into();
+			let mut cursor = chunk_leaf(v).into();
 			let mut next_nm = self.l_stack.name().map(|n|name_pair(n,nmtree.clone()));
-			while let Some((l_vec,next_lev)) = self.l_stack.next_archive() {
-				let l_curs = tree::Tree::new(0,None,TreeData::Leaf(Rc::new(l_vec)),None,None).unwrap().into();
+			while let Some((l_vec,next_lev)) = self.l_stack.next_chunk() {
+				let l_curs = chunk_leaf(l_vec).into();
 				cursor = tree::Cursor::join(l_curs,l_lev.unwrap(),l_nm,TreeData::Dummy,cursor);
 				l_lev = next_lev;
 				l_nm = next_nm;
 				next_nm = self.l_stack.name().map(|n|name_pair(n,nmtree.clone()));
 			}
 			next_nm = self.r_stack.name().map(|n|name_pair(n,nmtree.clone()));
-			while let Some((mut r_vec,next_lev)) = self.r_stack.next_archive() {
-				r_vec.reverse();
-				let r_curs = tree::Tree::new(0,None,TreeData::Leaf(Rc::new(r_vec)),None,None).unwrap().into();
+			while let Some((r_vec,next_lev)) = self.r_stack.next_chunk() {
+				let r_curs = chunk_leaf(r_vec.reversed()).into();
 				cursor = tree::Cursor::join(cursor,r_lev.unwrap(),r_nm,TreeData::Dummy,r_curs);
 				r_lev = next_lev;
 				r_nm = next_nm;
@@ -553,6 +1313,16 @@ Raz<E,M> {
 	// 	} 
 	// }
 
+	/// check the structure of the trees on both sides of the cursor
+	///
+	/// See `Cursor::validate`. This is an O(n) operation meant for
+	/// tests and debugging.
+	pub fn validate(&self) -> Vec<Invalid> {
+		let mut errors = self.l_forest.validate();
+		errors.extend(self.r_forest.validate());
+		errors
+	}
+
 	/// add an element to the left of the cursor
 	///
 	/// returns number of non-archived elements
@@ -560,6 +1330,16 @@ Raz<E,M> {
 		self.l_stack.push(elm);
 		self.l_stack.active_len()
 	}
+	/// add an element to the left of the cursor, archiving it if the
+	/// chunker finds a boundary after it
+	///
+	/// returns number of non-archived elements
+	pub fn push_left_chunked(&mut self, elm: E, chunker: &mut Chunker) -> usize {
+		let sep = chunker.push(&elm);
+		self.l_stack.push(elm);
+		if let Some((level,name)) = sep { self.archive_left(level, name) }
+		self.l_stack.active_len()
+	}
 	/// add an element to the right of the cursor
 	///
 	/// returns number of non-archived elements
@@ -597,46 +1377,400 @@ Raz<E,M> {
 	}
 	/// mark the data at the left to be part of a subsequence
 	pub fn archive_left(&mut self, level: u32, name: Option<Name>) {
+		self.close(true, false);
 		self.l_stack.archive(name,level);
 	}
 	/// mark the data at the right to be part of a subsequence
 	pub fn archive_right(&mut self, level: u32, name: Option<Name>) {
+		self.close(false, false);
 		self.r_stack.archive(name,level);
 	}
+	/// mark the data at the left to be part of a subsequence, with a
+	/// level derived from that data
+	///
+	/// If a name is given, it is paired with a hash of the data, so
+	/// the same names must not be given for equal subsequences.
+	pub fn archive_left_hashed(&mut self, name_base: Option<Name>) {
+		let (level, name) = hashed_sep(self.l_stack.active_data(), name_base);
+		self.archive_left(level, name);
+	}
+	/// mark the data at the right to be part of a subsequence, with a
+	/// level derived from that data
+	///
+	/// If a name is given, it is paired with a hash of the data, so
+	/// the same names must not be given for equal subsequences.
+	pub fn archive_right_hashed(&mut self, name_base: Option<Name>) {
+		let (level, name) = hashed_sep(&self.r_stack.active_data().clone().reversed(), name_base);
+		self.archive_right(level, name);
+	}
+
+	/// remove and return an element to the left of the cursor
+	///
+	/// if an archive point is to the left of the cursor, it is kept
+	/// and restored at the cursor by `unfocus`, so popping elements and
+	/// pushing them back leaves the sequence with the same tree
+	pub fn pop_left(&mut self) -> Option<E> {
+		if self.l_stack.active_len() == 0 && !self.l_stack.is_empty() {
+			// the archive below becomes a leaf, opened like the others
+			let nm = self.l_stack.name().map(|n|name_pair(n,name_of_string(String::from("tree"))));
+			let (_,lev) = self.l_stack.next_chunk().unwrap();
+			self.flush_left(lev.unwrap(), nm);
+		}
+		if self.l_stack.is_empty() {
+			if self.l_forest.up() == tree::UpResult::Fail { return None } else {
+				let sep = (self.l_forest.peek_level().unwrap(), self.l_forest.peek_name());
+				self.l_forest.down_left_force(tree::Force::Discard);
+				while self.l_forest.down_right() {}
+				match self.l_forest.peek() {
+					Some(TreeData::Leaf(ref data)) => {
+						reopen(&mut self.l_open, data.len(), sep);
+						self.l_stack.push_chunk(Chunk::from(data.clone()))
+					},
+					_ => panic!("pop_left: no left tree leaf"),
+				}
+			}
+		}
+		self.l_stack.pop()
+	}
+	/// remove and return an element to the right of the cursor
+	///
+	/// if an archive point is to the right of the cursor, it is kept
+	/// and restored at the cursor by `unfocus`, like `pop_left`
+	pub fn pop_right(&mut self) -> Option<E> {
+		if self.r_stack.active_len() == 0 && !self.r_stack.is_empty() {
+			let nm = self.r_stack.name().map(|n|name_pair(n,name_of_string(String::from("tree"))));
+			let (_,lev) = self.r_stack.next_chunk().unwrap();
+			self.flush_right(lev.unwrap(), nm);
+		}
+		if self.r_stack.is_empty() {
+			if self.r_forest.up() == tree::UpResult::Fail { return None } else {
+				let sep = (self.r_forest.peek_level().unwrap(), self.r_forest.peek_name());
+				self.r_forest.down_right_force(tree::Force::Discard);
+				while self.r_forest.down_left() {}
+				match self.r_forest.peek() {
+					Some(TreeData::Leaf(ref data)) => {
+						reopen(&mut self.r_open, data.len(), sep);
+						self.r_stack.push_chunk(Chunk::from(data.clone()).reversed())
+					},
+					_ => panic!("pop_right: no right tree leaf"),
+				}
+			}
+		}
+		self.r_stack.pop()
+	}
+
+	// restores the archive points opened by popping
+	fn settle(&mut self) {
+		self.close(true, true);
+		self.close(false, true);
+	}
+
+	// moves the leaves opened by popping on the left or right back
+	// into the forest, leaving any elements past the last archive
+	// point on the stack unless `at_end`, which uses the nearest one
+	// for them
+	fn close(&mut self, left: bool, at_end: bool) {
+		let (stack, open) = if left {
+			(&mut self.l_stack, &mut self.l_open)
+		} else {
+			(&mut self.r_stack, &mut self.r_open)
+		};
+		if open.is_empty() { return }
+		// the stack has no archives while leaves are open
+		let mut rest = stack.next_chunk().map_or(Chunk::new(), |a| a.0);
+		let mut leaves = Vec::new();
+		let mut done = 0;
+		for (offset, lev, nm) in mem::take(open) {
+			if rest.is_empty() || (offset - done >= rest.len() && !at_end) { break }
+			let split = ::std::cmp::min(offset - done, rest.len());
+			let tail = rest.slice(split..rest.len());
+			leaves.push((rest.slice(0..split), lev, nm));
+			rest = tail;
+			done = offset;
+		}
+		stack.push_chunk(rest);
+		for (chunk, lev, nm) in leaves {
+			if left {
+				self.append_left(chunk_leaf(chunk), lev, nm)
+			} else {
+				self.prepend_right(chunk_leaf(chunk.reversed()), lev, nm)
+			}
+		}
+	}
+
+	// moves the left stack into the left forest, followed by the archive point
+	fn flush_left(&mut self, level: u32, name: Option<Name>) {
+		let nmtree = name_of_string(String::from("tree"));
+		let mut sep = Some((level, name));
+		let mut chunks = Vec::new();
+		loop {
+			let nm = self.l_stack.name().map(|n|name_pair(n,nmtree.clone()));
+			match self.l_stack.next_chunk() {
+				None => break,
+				// an archive point next to the new one is dropped
+				Some((ref vec, _)) if vec.is_empty() => {},
+				Some((vec, lev)) => {
+					chunks.push((vec, sep.take().unwrap()));
+					sep = lev.map(|lev| (lev, nm));
+				},
+			}
+		}
+		for (vec, (lev, nm)) in chunks.into_iter().rev() {
+			self.append_left(chunk_leaf(vec), lev, nm);
+		}
+	}
+
+	// moves the right stack into the right forest, after the archive point
+	fn flush_right(&mut self, level: u32, name: Option<Name>) {
+		let nmtree = name_of_string(String::from("tree"));
+		let mut sep = Some((level, name));
+		let mut chunks = Vec::new();
+		loop {
+			let nm = self.r_stack.name().map(|n|name_pair(n,nmtree.clone()));
+			match self.r_stack.next_chunk() {
+				None => break,
+				// an archive point next to the new one is dropped
+				Some((ref vec, _)) if vec.is_empty() => {},
+				Some((vec, lev)) => {
+					chunks.push((vec.reversed(), sep.take().unwrap()));
+					sep = lev.map(|lev| (lev, nm));
+				},
+			}
+		}
+		for (vec, (lev, nm)) in chunks.into_iter().rev() {
+			self.prepend_right(chunk_leaf(vec), lev, nm);
+		}
+	}
+
+	// adds a tree at the end of the left forest, followed by the archive point
+	fn append_left(&mut self, t: tree::Tree<TreeData<E,M>>, level: u32, name: Option<Name>) {
+		let mut forest = mem::replace(&mut self.l_forest, tree::Cursor::new());
+		let joined = if forest.up() == tree::UpResult::Fail {
+			tree::Cursor::from(t)
+		} else {
+			let lev = forest.peek_level().unwrap();
+			let nm = forest.peek_name();
+			forest.down_left_force(tree::Force::Discard);
+			tree::Cursor::join(forest, lev, nm, TreeData::Dummy, tree::Cursor::from(t))
+		};
+		let mut forest = tree::Cursor::join(joined, level, name, TreeData::Dummy, tree::Cursor::new());
+		assert!(forest.down_right_force(tree::Force::Yes));
+		self.l_forest = forest;
+	}
+
+	// adds a tree at the start of the right forest, after the archive point
+	fn prepend_right(&mut self, t: tree::Tree<TreeData<E,M>>, level: u32, name: Option<Name>) {
+		let mut forest = mem::replace(&mut self.r_forest, tree::Cursor::new());
+		let joined = if forest.up() == tree::UpResult::Fail {
+			tree::Cursor::from(t)
+		} else {
+			let lev = forest.peek_level().unwrap();
+			let nm = forest.peek_name();
+			forest.down_right_force(tree::Force::Discard);
+			tree::Cursor::join(tree::Cursor::from(t), lev, nm, TreeData::Dummy, forest)
+		};
+		let mut forest = tree::Cursor::join(tree::Cursor::new(), level, name, TreeData::Dummy, joined);
+		assert!(forest.down_left_force(tree::Force::Yes));
+		self.r_forest = forest;
+	}
+}
+
+impl<E: Debug+Clone+Eq+Hash+'static> Raz<E,Count> {
+	/// the number of elements to the left of the cursor
+	pub fn position(&self) -> usize {
+		let mut l_stack = self.l_stack.clone();
+		let mut len = 0;
+		while let Some((vec,_)) = l_stack.next_chunk() { len += vec.len() }
+		let mut l_forest = self.l_forest.clone();
+		loop { match l_forest.up_discard() {
+			tree::UpResult::Fail => break,
+			tree::UpResult::Left => len += l_forest.left_tree().map_or(0, |t|count_of(&t)),
+			tree::UpResult::Right => {},
+		}}
+		len
+	}
+
+	/// move the cursor to the left by `k` elements, returning the
+	/// number of elements moved
+	///
+	/// Archived subsequences and whole subtrees are moved past the
+	/// cursor, keeping their levels and names, so only the leaf at the
+	/// new location is opened. Stops at the start of the sequence.
+	pub fn move_left(&mut self, k: usize) -> usize {
+		self.settle();
+		let mut moved = 0;
+		// step 1: move data from the stack, with its archive points
+		while moved < k && !self.l_stack.is_empty() {
+			if k - moved < self.l_stack.active_len() {
+				for _ in 0..(k - moved) {
+					let elm = self.l_stack.pop().unwrap();
+					self.r_stack.push(elm);
+				}
+				moved = k;
+			} else {
+				let name = self.l_stack.name();
+				let (vec, lev) = self.l_stack.next_chunk().unwrap();
+				moved += vec.len();
+				self.r_stack.push_chunk(vec.reversed());
+				if let Some(lev) = lev { self.r_stack.archive(name, lev); }
+			}
+		}
+		if moved == k { return moved }
+		// step 2: move up the forest, past subtrees smaller than the distance
+		let mut first_sep = None;
+		let mut moving = Vec::new();
+		let mut pending: Option<CountTree<E>> = None;
+		let mut remaining = None;
+		while self.l_forest.up() != tree::UpResult::Fail {
+			let sep = (self.l_forest.peek_level().unwrap(), self.l_forest.peek_name());
+			match pending.take() {
+				None => first_sep = Some(sep),
+				Some(t) => moving.push((t, sep)),
+			}
+			assert!(self.l_forest.down_left_force(tree::Force::Discard));
+			let t = self.l_forest.at_tree().unwrap();
+			let count = count_of(&t);
+			if k - moved > count {
+				moved += count;
+				pending = Some(t);
+			} else {
+				remaining = Some(k - moved);
+				break;
+			}
+		}
+		// at the start, the last subtree is the destination
+		let mut remaining = match (remaining, pending) {
+			(Some(r), _) => r,
+			(None, Some(t)) => {
+				let count = count_of(&t);
+				moved -= count;
+				count
+			},
+			(None, None) => return moved,
+		};
+		moved += remaining;
+		// step 3: move down to the destination leaf
+		while let Some(TreeData::Branch(_, Count(r))) = self.l_forest.peek() {
+			if remaining <= r {
+				assert!(self.l_forest.down_right());
+			} else {
+				let sep = (self.l_forest.peek_level().unwrap(), self.l_forest.peek_name());
+				moving.push((self.l_forest.right_tree().unwrap(), sep));
+				remaining -= r;
+				assert!(self.l_forest.down_left_force(tree::Force::Discard));
+			}
+		}
+		let vec = match self.l_forest.peek() {
+			Some(TreeData::Leaf(vec)) => vec,
+			_ => panic!("move_left: no destination leaf"),
+		};
+		// step 4: rebuild the right side and split the leaf
+		let (lev, nm) = first_sep.unwrap();
+		self.flush_right(lev, nm);
+		for (t, (lev, nm)) in moving { self.prepend_right(t, lev, nm) }
+		let split = vec.len() - remaining;
+		let chunk = Chunk::from(vec);
+		self.l_stack.push_chunk(chunk.slice(0..split));
+		self.r_stack.push_chunk(chunk.slice(split..chunk.len()).reversed());
+		moved
+	}
 
-	/// remove and return an element to the left of the cursor
+	/// move the cursor to the right by `k` elements, returning the
+	/// number of elements moved
 	///
-	/// if an archive point is to the left of the cursor,
-	/// it will be removed
-	pub fn pop_left(&mut self) -> Option<E> {
-		if self.l_stack.is_empty() {
-			if self.l_forest.up() == tree::UpResult::Fail { return None } else {
-				self.l_forest.down_left_force(tree::Force::Discard);
-				while self.l_forest.down_right() {}
-				match self.l_forest.peek() {
-					Some(TreeData::Leaf(ref data)) => self.l_stack.extend(&***data),
-					_ => panic!("pop_left: no left tree leaf"),
+	/// Like `move_left`, this keeps archive points and names and only
+	/// opens the leaf at the new location. Stops at the end of the sequence.
+	pub fn move_right(&mut self, k: usize) -> usize {
+		self.settle();
+		let mut moved = 0;
+		// step 1: move data from the stack, with its archive points
+		while moved < k && !self.r_stack.is_empty() {
+			if k - moved < self.r_stack.active_len() {
+				for _ in 0..(k - moved) {
+					let elm = self.r_stack.pop().unwrap();
+					self.l_stack.push(elm);
 				}
+				moved = k;
+			} else {
+				let name = self.r_stack.name();
+				let (vec, lev) = self.r_stack.next_chunk().unwrap();
+				moved += vec.len();
+				self.l_stack.push_chunk(vec.reversed());
+				if let Some(lev) = lev { self.l_stack.archive(name, lev); }
 			}
 		}
-		self.l_stack.pop()
+		if moved == k { return moved }
+		// step 2: move up the forest, past subtrees smaller than the distance
+		let mut first_sep = None;
+		let mut moving = Vec::new();
+		let mut pending: Option<CountTree<E>> = None;
+		let mut remaining = None;
+		while self.r_forest.up() != tree::UpResult::Fail {
+			let sep = (self.r_forest.peek_level().unwrap(), self.r_forest.peek_name());
+			match pending.take() {
+				None => first_sep = Some(sep),
+				Some(t) => moving.push((t, sep)),
+			}
+			assert!(self.r_forest.down_right_force(tree::Force::Discard));
+			let t = self.r_forest.at_tree().unwrap();
+			let count = count_of(&t);
+			if k - moved > count {
+				moved += count;
+				pending = Some(t);
+			} else {
+				remaining = Some(k - moved);
+				break;
+			}
+		}
+		// at the end, the last subtree is the destination
+		let mut remaining = match (remaining, pending) {
+			(Some(r), _) => r,
+			(None, Some(t)) => {
+				let count = count_of(&t);
+				moved -= count;
+				count
+			},
+			(None, None) => return moved,
+		};
+		moved += remaining;
+		// step 3: move down to the destination leaf
+		while let Some(TreeData::Branch(Count(l), _)) = self.r_forest.peek() {
+			if remaining <= l {
+				assert!(self.r_forest.down_left());
+			} else {
+				let sep = (self.r_forest.peek_level().unwrap(), self.r_forest.peek_name());
+				moving.push((self.r_forest.left_tree().unwrap(), sep));
+				remaining -= l;
+				assert!(self.r_forest.down_right_force(tree::Force::Discard));
+			}
+		}
+		let vec = match self.r_forest.peek() {
+			Some(TreeData::Leaf(vec)) => vec,
+			_ => panic!("move_right: no destination leaf"),
+		};
+		// step 4: rebuild the left side and split the leaf
+		let (lev, nm) = first_sep.unwrap();
+		self.flush_left(lev, nm);
+		for (t, (lev, nm)) in moving { self.append_left(t, lev, nm) }
+		let chunk = Chunk::from(vec);
+		self.l_stack.push_chunk(chunk.slice(0..remaining));
+		self.r_stack.push_chunk(chunk.slice(remaining..chunk.len()).reversed());
+		moved
 	}
-	/// remove and return an element to the right of the cursor
+
+	/// move the cursor to the given position, so that `index`
+	/// elements are to its left
 	///
-	/// if an archive point is to the right of the cursor,
-	/// it will be removed
-	pub fn pop_right(&mut self) -> Option<E> {
-		if self.r_stack.is_empty() {
-			if self.r_forest.up() == tree::UpResult::Fail { return None } else {
-				self.r_forest.down_right_force(tree::Force::Discard);
-				while self.r_forest.down_left() {}
-				match self.r_forest.peek() {
-					Some(TreeData::Leaf(ref data)) => self.r_stack.extend_rev(&***data),
-					_ => panic!("pop_right: no right tree leaf"),
-				}
-			}
+	/// Returns false if the index is past the end of the sequence,
+	/// leaving the cursor at the end.
+	pub fn seek(&mut self, index: usize) -> bool {
+		let pos = self.position();
+		if index < pos {
+			self.move_left(pos - index);
+			true
+		} else {
+			self.move_right(index - pos) == index - pos
 		}
-		self.r_stack.pop()
 	}
 }
 
@@ -682,11 +1816,200 @@ Raz<E,M> {
 use level_tree as ltree;
 use std::convert::From;
 
+/// keeps the archive point of a leaf opened by popping into an empty
+/// stack, below those kept for the leaves opened before it
+fn reopen(open: &mut Vec<(usize,u32,Option<Name>)>, len: usize, (level, name): (u32,Option<Name>)) {
+	for o in open.iter_mut() { o.0 += len }
+	open.insert(0, (len, level, name));
+}
+
+/// level and name of a separator, derived from the data before it
+fn hashed_sep<E: Debug+Clone+Eq+Hash+'static>(data: &Chunk<E>, name_base: Option<Name>) -> (u32,Option<Name>) {
+	(::hash_level(data), name_base.map(|n| name_pair(n, ::hash_name(data))))
+}
+
 /// convenience fn for making a tree from data
 #[allow(unused)]
 fn leaf<E: Debug+Clone+Eq+Hash+'static,M:RazMeta<E>>(v:Vec<E>, n: Option<Name>) -> ltree::Tree<TreeData<E,M>> {
 	ltree::Tree::new(0,n,TreeData::Leaf(Rc::new(v)),None,None).unwrap()
 }
+/// a leaf sharing the data of a chunk, if it covers a whole vector
+fn chunk_leaf<E: Debug+Clone+Eq+Hash+'static,M:RazMeta<E>>(c: Chunk<E>) -> ltree::Tree<TreeData<E,M>> {
+	ltree::Tree::new(0,None,TreeData::Leaf(c.into_rc()),None,None).unwrap()
+}
+/// leaves of a tree, with the level and name of each separator between them
+type TreeParts<E,M> = (Vec<ltree::Tree<TreeData<E,M>>>, Vec<(u32,Option<Name>)>);
+/// subtrees waiting for a right branch, with the level and name of their parent
+type Spine<E,M> = Vec<(ltree::Tree<TreeData<E,M>>,u32,Option<Name>)>;
+/// directions to a named node, with the elements before it and the node
+type NamePath<E> = (Vec<bool>,usize,ltree::Tree<TreeData<E,Names>>);
+/// tree with element counts, for splitting by position
+type CountTree<E> = ltree::Tree<TreeData<E,Count>>;
+
+/// summary of a subtree for `RazTree::windows`
+#[derive(Clone,Eq,PartialEq,Hash,Debug)]
+struct Windowed<E: Debug+Clone+Eq+Hash+'static, R: Debug+Clone+Eq+Hash+'static, N: RazMeta<R>+'static> {
+	len: usize,
+	// up to k-1 elements from each end
+	first: Vec<E>,
+	last: Vec<E>,
+	// the windows contained in the subtree
+	out: Option<ltree::Tree<TreeData<R,N>>>,
+}
+
+/// combines two optional trees, as a branch if both exist
+fn join_opt<E: Debug+Clone+Eq+Hash+'static,M:RazMeta<E>>(
+	t1: Option<ltree::Tree<TreeData<E,M>>>,
+	l:  u32,
+	n:  Option<Name>,
+	t2: Option<ltree::Tree<TreeData<E,M>>>
+) -> Option<ltree::Tree<TreeData<E,M>>> {
+	match (t1,t2) {
+		(None,None) => None,
+		(Some(t),None) | (None,Some(t)) => Some(t),
+		(Some(t1),Some(t2)) => Some(bin(t1,l,n,t2)),
+	}
+}
+
+/// the number of elements in a tree with `Count` metadata
+fn count_of<E: Debug+Clone+Eq+Hash+'static>(t: &CountTree<E>) -> usize {
+	match t.peek() {
+		TreeData::Leaf(ref vec) => vec.len(),
+		TreeData::Branch(Count(l),Count(r)) => l + r,
+		TreeData::Dummy => unreachable!(),
+	}
+}
+
+/// splits a tree into the first `index` elements and the rest
+///
+/// Subtrees that are not split are shared. Branches rebuilt along
+/// the split are given no names.
+fn split_count<E: Debug+Clone+Eq+Hash+'static>(
+	t: Option<CountTree<E>>,
+	index: usize,
+) -> (Option<CountTree<E>>,Option<CountTree<E>>) {
+	let t = match t { None => return (None,None), Some(t) => t };
+	if index == 0 { return (None,Some(t)) }
+	if index >= count_of(&t) { return (Some(t),None) }
+	let join = |l: Option<ltree::Tree<_>>, r: Option<ltree::Tree<_>>| match (l,r) {
+		(None,None) => None,
+		(Some(t),None) | (None,Some(t)) => Some(t),
+		(Some(l),Some(r)) => Some(bin(l,t.level(),None,r)),
+	};
+	match t.peek() {
+		TreeData::Leaf(ref vec) => {
+			(Some(leaf(vec[..index].to_vec(),None)), Some(leaf(vec[index..].to_vec(),None)))
+		},
+		TreeData::Branch(Count(l_count),_) => {
+			if index <= l_count {
+				let (a,b) = split_count(t.l_tree(), index);
+				(a, join(b,t.r_tree()))
+			} else {
+				let (a,b) = split_count(t.r_tree(), index - l_count);
+				(join(t.l_tree(),a), b)
+			}
+		},
+		TreeData::Dummy => unreachable!(),
+	}
+}
+
+/// collects the first `limit` elements of a tree, left to right
+fn collect_leaves<E: Debug+Clone+Eq+Hash+'static, M:RazMeta<E>>(t: &ltree::Tree<TreeData<E,M>>, limit: usize, out: &mut Vec<E>) {
+	if out.len() >= limit { return }
+	if let TreeData::Leaf(ref vec) = t.peek() {
+		let more = ::std::cmp::min(vec.len(), limit - out.len());
+		out.extend_from_slice(&vec[..more])
+	}
+	if let Some(l) = t.l_tree() { collect_leaves(&l, limit, out) }
+	if let Some(r) = t.r_tree() { collect_leaves(&r, limit, out) }
+}
+
+/// zips a tree with the aligned part of another, used by `RazTree::zip_with`
+fn zip_tree<E,E2,R,F,N>(
+	t: CountTree<E>,
+	other: Option<CountTree<E2>>,
+	f: Rc<F>,
+) -> Option<ltree::Tree<TreeData<R,N>>> where
+	E: 'static + Eq+Clone+Hash+Debug,
+	E2: 'static + Eq+Clone+Hash+Debug,
+	R: 'static + Eq+Clone+Hash+Debug,
+	F: 'static + Fn(&E,&E2) -> R,
+	N: RazMeta<R>,
+{
+	let other = other?;
+	match t.peek() {
+		TreeData::Leaf(ref vec) => {
+			let mut others = Vec::with_capacity(vec.len());
+			collect_leaves(&other, vec.len(), &mut others);
+			let zipped = vec.iter().zip(others.iter()).map(|(a,b)|f(a,b)).collect::<Vec<_>>();
+			if zipped.is_empty() { None } else { Some(leaf(zipped,t.name())) }
+		},
+		TreeData::Branch(Count(l_count),_) => {
+			let (ol,or) = split_count(Some(other), l_count);
+			let (l,r) = match t.name() {
+				None => (
+					t.l_tree().and_then(|lt| zip_tree(lt, ol, f.clone())),
+					t.r_tree().and_then(|rt| zip_tree(rt, or, f.clone())),
+				),
+				Some(name) => {
+					let (n1, n2) = name_fork(name);
+					(
+						t.l_tree().and_then(|lt| memo!( n1 =>> zip_tree, t:lt, o:ol ;; f:f.clone() )),
+						t.r_tree().and_then(|rt| memo!( n2 =>> zip_tree, t:rt, o:or ;; f:f.clone() )),
+					)
+				},
+			};
+			match (l,r) {
+				(None,None) => None,
+				(Some(t),None) | (None,Some(t)) => Some(t),
+				(Some(l),Some(r)) => Some(bin(l,t.level(),t.name(),r)),
+			}
+		},
+		TreeData::Dummy => unreachable!(),
+	}
+}
+
+/// joins two cannonical trees around a new branch, keeping the result cannonical
+///
+/// The top of the result is the first branch of highest level. Branches
+/// rebuilt along the inner spines get names paired with the new branch's name.
+fn rev_join<E: Debug+Clone+Eq+Hash+'static,M:RazMeta<E>>(
+	t1: Option<ltree::Tree<TreeData<E,M>>>,
+	l:  u32,
+	n:  Option<Name>,
+	t2: Option<ltree::Tree<TreeData<E,M>>>
+) -> ltree::Tree<TreeData<E,M>> {
+	let top = |t: &Option<ltree::Tree<TreeData<E,M>>>| match *t {
+		Some(ref t) => match t.peek() { TreeData::Leaf(_) => None, _ => Some(t.level()) },
+		None => None,
+	};
+	let rename = |old: Option<Name>| match (old, n.clone()) {
+		(Some(old),Some(n)) => Some(name_pair(old,n)),
+		_ => None,
+	};
+	let node = |t1: Option<ltree::Tree<TreeData<E,M>>>, l, n: Option<Name>, t2: Option<ltree::Tree<TreeData<E,M>>>| {
+		let td = TreeData::rebuild(
+			t1.as_ref().map(|t| t.peek()).as_ref(),
+			&TreeData::Dummy,
+			l, n.clone(),
+			t2.as_ref().map(|t| t.peek()).as_ref(),
+		);
+		ltree::Tree::new(l,n,td,t1,t2).unwrap()
+	};
+	match (top(&t1), top(&t2)) {
+		(Some(l1), l2) if l1 >= l && l1 >= l2.unwrap_or(0) => {
+			let t1 = t1.unwrap();
+			let r = rev_join(t1.r_tree(), l, n.clone(), t2);
+			node(t1.l_tree(), t1.level(), rename(t1.name()), Some(r))
+		},
+		(_, Some(l2)) if l2 > l => {
+			let t2 = t2.unwrap();
+			let lt = rev_join(t1, l, n.clone(), t2.l_tree());
+			node(Some(lt), t2.level(), rename(t2.name()), t2.r_tree())
+		},
+		_ => node(t1, l, n, t2),
+	}
+}
 /// convenience fn for combining two trees as branches
 #[allow(unused)]
 fn bin<E: Debug+Clone+Eq+Hash+'static,M:RazMeta<E>>(
@@ -738,8 +2061,8 @@ for RazTree<E,M> {
 			assert!(accum_tree.level() <= first_level);
 			assert!(first_level < max_level);
 			let next_name = stack.name();
-			let (vec,next_level) = stack.next_archive().unwrap_or_else(||{panic!("stack was unexpectedly empty")});
-			let leaf_tree = leaf(vec,None);
+			let (vec,next_level) = stack.next_chunk().unwrap_or_else(||{panic!("stack was unexpectedly empty")});
+			let leaf_tree = chunk_leaf(vec);
 			let (shorter_stack, final_level,final_name,small_tree) = match next_level {
 				None =>
 					(stack::AStack::new(),None,None,leaf_tree),
@@ -762,13 +2085,13 @@ for RazTree<E,M> {
 		}
 		let mut tailstack = tailstack.0.clone();
 		let name = tailstack.name();
-		let (level, first_tree) = match tailstack.next_archive() {
+		let (level, first_tree) = match tailstack.next_chunk() {
 			None => return RazTree{meta: treetop_meta(None), tree: None},
 			Some((vec,None)) => {
-				let t = Some(leaf(vec,None));
+				let t = Some(chunk_leaf(vec));
 				return RazTree{meta: treetop_meta(t.as_ref()), tree: t}
 			},
-			Some((vec,Some(level))) => (level,leaf(vec,None))
+			Some((vec,Some(level))) => (level,chunk_leaf(vec))
 		};
 		let (s,l,n,t) = from_stack_memo(tailstack, level, name, first_tree, u32::max_value());
 		assert!(l.is_none());
@@ -813,9 +2136,8 @@ for RazTree<E,M> {
 			assert!(accum_tree.level() < first_level);
 			assert!(first_level <= max_level);
 			let next_name = stack.name();
-			let (mut vec,next_level) = stack.next_archive().unwrap_or_else(||{panic!("stack was unexpectedly empty")});
-			vec.reverse();
-			let leaf_tree = leaf(vec,None);
+			let (vec,next_level) = stack.next_chunk().unwrap_or_else(||{panic!("stack was unexpectedly empty")});
+			let leaf_tree = chunk_leaf(vec.reversed());
 			let (shorter_stack, final_level,final_name,small_tree) = match next_level {
 				None =>
 					(stack::AStack::new(),None,None,leaf_tree),
@@ -838,17 +2160,13 @@ for RazTree<E,M> {
 		}
 		let mut headstack = headstack.0.clone();
 		let name = headstack.name();
-		let (level, first_tree) = match headstack.next_archive() {
+		let (level, first_tree) = match headstack.next_chunk() {
 			None => return RazTree{meta: treetop_meta(None), tree: None},
-			Some((mut vec,None)) => {
-				vec.reverse();
-				let t = Some(leaf(vec,None));
+			Some((vec,None)) => {
+				let t = Some(chunk_leaf(vec.reversed()));
 				return RazTree{meta: treetop_meta(t.as_ref()), tree: t}
 			},
-			Some((mut vec,Some(level))) => {
-				vec.reverse();
-				(level,leaf(vec,None))
-			}
+			Some((vec,Some(level))) => (level,chunk_leaf(vec.reversed())),
 		};
 		let (s,l,n,t) = from_stack_memo(headstack, level, name, first_tree, u32::max_value());
 		assert!(l.is_none());
@@ -1448,6 +2766,65 @@ mod tests {
 		}
 	}
 
+	#[test]
+	fn test_get_by_name() {
+		let mut r: Raz<_,Names> = Raz::new();
+		for i in 0..1000 {
+			r.push_left(i);
+			if i % 10 == 9 { r.archive_left(::inc_level(),Some(name_of_usize(i))) }
+		}
+		let t = r.unfocus();
+		assert!(t.validate().is_empty());
+		assert_eq!(1000, t.meta().count());
+		assert_eq!(99, t.meta().len());
+		assert_eq!(99, t.meta().names().len());
+		let tree_name = |i| name_pair(name_of_usize(i),name_of_string(String::from("tree")));
+		for i in (9..990).step_by(70) {
+			let range = t.get_by_name(&tree_name(i)).unwrap();
+			assert!(range.start <= i && i < range.end, "{} not in {:?}", i, range);
+		}
+		// focusing may rebuild named nodes, so this is done after
+		for i in (9..990).step_by(70) {
+			let raz = t.clone().focus_name(&tree_name(i)).unwrap();
+			assert_eq!(Some(i), raz.peek_left());
+			assert_eq!(Some(i+1), raz.peek_right());
+		}
+		assert_eq!(None, t.get_by_name(&name_of_usize(9)));
+		assert!(t.clone().focus_name(&name_of_usize(9)).is_none());
+
+		// the meta compares by content, whatever the tree's shape
+		let mut r = t.clone().focus_name(&tree_name(499)).unwrap();
+		let again = r.clone().unfocus();
+		assert_eq!(t.meta(), again.meta());
+		assert_eq!(::hash64(t.meta()), ::hash64(again.meta()));
+		r.push_left(1000);
+		assert!(t.meta() != r.unfocus().meta());
+		let mut r: Raz<_,Names> = Raz::new();
+		for i in 0..1000 {
+			r.push_left(i);
+			if i % 10 == 9 { r.archive_left(::inc_level(),Some(name_of_usize(if i == 989 {1} else {i}))) }
+		}
+		let u = r.unfocus();
+		assert_eq!((t.meta().count(), t.meta().len()), (u.meta().count(), u.meta().len()));
+		assert!(t.meta() != u.meta());
+
+		// names on leaves
+		let (a,b,c) = (name_of_usize(1), name_of_usize(2), name_of_usize(3));
+		let build = || {
+			let tree = bin(leaf(vec![1,2],Some(a.clone())), 1, Some(b.clone()), leaf(vec![3,4,5],Some(c.clone())));
+			RazTree::<usize,Names>{meta: treetop_meta(Some(&tree)), tree: Some(tree)}
+		};
+		let t = build();
+		assert_eq!(Some(0..2), t.get_by_name(&a));
+		assert_eq!(Some(0..5), t.get_by_name(&b));
+		assert_eq!(Some(2..5), t.get_by_name(&c));
+		let raz = t.focus_name(&a).unwrap();
+		assert_eq!((Some(2),Some(3)), (raz.peek_left(), raz.peek_right()));
+		let raz = build().focus_name(&c).unwrap();
+		assert_eq!((Some(5),None), (raz.peek_left(), raz.peek_right()));
+		assert_eq!(Some(0..5), raz.unfocus().get_by_name(&b));
+	}
+
 	#[test]
 	fn test_peek_pop() {
 		let tree = example_tree();
@@ -1469,4 +2846,774 @@ mod tests {
 		}
 		assert!(count == 6);
 	}
+
+	#[test]
+	fn test_pop_restore() {
+		let mut r: Raz<usize,Count> = Raz::new();
+		for i in 0..100 {
+			r.push_left(i);
+			if i % 10 == 9 { r.archive_left(::inc_level(),Some(name_of_usize(i))) }
+		}
+		let tree = r.unfocus();
+
+		// through leaves of the tree
+		let mut raz = tree.clone().focus(45usize).unwrap();
+		let left = (0..23).map(|_| raz.pop_left().unwrap()).collect::<Vec<_>>();
+		let right = (0..31).map(|_| raz.pop_right().unwrap()).collect::<Vec<_>>();
+		for e in left.into_iter().rev() { raz.push_left(e); }
+		for e in right.into_iter().rev() { raz.push_right(e); }
+		assert_eq!(tree, raz.unfocus());
+
+		// through archives of the stacks
+		let mut raz = tree.clone().focus(45usize).unwrap();
+		raz.move_left(12);
+		raz.move_right(24);
+		let left = (0..15).map(|_| raz.pop_left().unwrap()).collect::<Vec<_>>();
+		for e in left.into_iter().rev() { raz.push_left(e); }
+		assert_eq!(tree, raz.unfocus());
+
+		// through archives that are still on the stacks
+		let mut r: Raz<usize,Count> = Raz::new();
+		for i in 0..100 {
+			r.push_left(i);
+			if i % 10 == 9 { r.archive_left(::inc_level(),Some(name_of_usize(i))) }
+			r.push_right(i);
+			if i % 10 == 9 { r.archive_right(::inc_level(),Some(name_of_usize(i))) }
+		}
+		let built = r.clone().unfocus();
+		let left = (0..25).map(|_| r.pop_left().unwrap()).collect::<Vec<_>>();
+		let right = (0..37).map(|_| r.pop_right().unwrap()).collect::<Vec<_>>();
+		for e in left.into_iter().rev() { r.push_left(e); }
+		for e in right.into_iter().rev() { r.push_right(e); }
+		assert_eq!(built, r.unfocus());
+	}
+
+	#[test]
+	fn test_focus_shares() {
+		fn leaves(t: &ltree::Tree<TreeData<usize,Count>>, out: &mut Vec<Rc<Vec<usize>>>) {
+			if let TreeData::Leaf(vec) = t.peek() { out.push(vec) }
+			if let Some(l) = t.l_tree() { leaves(&l, out) }
+			if let Some(r) = t.r_tree() { leaves(&r, out) }
+		}
+		let mut r: Raz<usize,Count> = Raz::new();
+		for i in 0..1000 {
+			r.push_left(i);
+			if i % 100 == 99 { r.archive_left(::inc_level(),Some(name_of_usize(i))) }
+		}
+		let tree = r.unfocus();
+		let mut before = Vec::new();
+		leaves(tree.tree.as_ref().unwrap(), &mut before);
+
+		// moving and refocusing keeps every leaf's data
+		let mut raz = tree.focus(450usize).unwrap();
+		raz.move_left(230);
+		raz.move_right(610);
+		let mut after = Vec::new();
+		leaves(raz.unfocus().tree.as_ref().unwrap(), &mut after);
+		assert_eq!(before.len(), after.len());
+		assert!(before.iter().zip(after.iter()).all(|(b,a)| Rc::ptr_eq(b,a)));
+	}
+
+	#[test]
+	fn test_from_iter_gauged() {
+		let tree: RazTree<usize,Count> = RazTree::from_iter_gauged(0..1005, 10, Some(name_of_string(String::from("gauged"))));
+		assert!(tree.validate().is_empty());
+		assert_eq!(1005, tree.meta().0);
+		assert_eq!((0..1005).collect::<Vec<_>>(), tree.clone().fold_lr(Vec::new(), Rc::new(|mut v: Vec<usize>, e: &usize| { v.push(*e); v })));
+		let seps = ns(name_of_string(String::from("seps")),||{ tree.clone().fold_lr_meta(
+			Vec::new(),
+			Rc::new(|a,_e:&usize|{a}),
+			Rc::new(|mut a:Vec<Option<Name>>,(_l,n):(_,Option<Name>)|{a.push(n);a}),
+		) });
+		assert_eq!(100, seps.len());
+		assert!(seps.iter().all(|n| n.is_some()));
+
+		// refocusing keeps the structure
+		let raz = tree.clone().focus(503usize).unwrap();
+		assert_eq!(tree, raz.unfocus());
+
+		let collected: RazTree<usize,Count> = (0..250).collect();
+		assert!(collected.validate().is_empty());
+		assert_eq!(250, collected.meta().0);
+		let empty: RazTree<usize,Count> = RazTree::from_iter_gauged(Vec::new(), 10, None);
+		assert!(empty.is_empty());
+	}
+
+	#[test]
+	fn test_from_iter_hashed() {
+		let data = (0..3000).map(|i| i * 7919 % 10007).collect::<Vec<usize>>();
+		let mut edited = data.clone();
+		edited.insert(1500, 42);
+		let base = || Some(name_of_string(String::from("hashed")));
+		let tree: RazTree<usize,Count> = RazTree::from_iter_hashed(data.clone(), 20, base());
+		assert!(tree.validate().is_empty());
+		assert_eq!(data, tree.clone().fold_lr(Vec::new(), Rc::new(|mut v: Vec<usize>, e: &usize| { v.push(*e); v })));
+		// the same data always gives the same tree
+		assert_eq!(tree, RazTree::from_iter_hashed(data.clone(), 20, base()));
+		// with levels that keep it balanced, for any data
+		assert!(tree.depth() <= 3 * tree.stats().balanced_depth);
+		let seq: RazTree<usize,Count> = RazTree::from_iter_hashed(0..100_000, 16, None);
+		assert!(seq.depth() <= 3 * seq.stats().balanced_depth, "depth {}", seq.depth());
+
+		// an insert only changes nearby separators
+		let seps = |t: RazTree<usize,Count>, nm: &str| ns(name_of_string(String::from(nm)),||{ t.fold_lr_meta(
+			Vec::new(),
+			Rc::new(|a,_e:&usize|{a}),
+			Rc::new(|mut a:Vec<(u32,Option<Name>)>,(l,n):(u32,Option<Name>)|{a.push((l,n));a}),
+		) });
+		let before = seps(tree, "before");
+		let after = seps(RazTree::from_iter_hashed(edited, 20, base()), "after");
+		assert!(before.len() > 50);
+		let changed = after.iter().filter(|s| !before.contains(s)).count();
+		assert!(changed <= 2, "{} separators changed", changed);
+
+		// hashed archives in a raz
+		let build = || {
+			let mut r: Raz<usize,Count> = Raz::new();
+			for i in 0..100 {
+				r.push_left(i);
+				if i % 10 == 9 { r.archive_left_hashed(Some(name_of_usize(i))) }
+			}
+			r.unfocus()
+		};
+		let t = build();
+		assert!(t.validate().is_empty());
+		assert_eq!(t, build());
+		let levels = seps(t, "raz");
+		assert_eq!(::hash_level(&Chunk::from((0..10).collect::<Vec<usize>>())), levels[0].0);
+
+		// hashes are FNV-1a and fmix64, with sizes as 64 bits on every platform
+		assert_eq!(0x3aec_69d2_86fb_d39b, ::hash64(&"a"));
+		assert_eq!(0xc211_2d51_b876_518d, ::hash64(&7usize));
+		assert_eq!(::hash64(&vec![7u64]), ::hash64(&vec![7usize]));
+	}
+
+	#[test]
+	fn test_from_iter_chunked() {
+		let data = (0..3000).map(|i| i * 7919 % 10007).collect::<Vec<usize>>();
+		let base = || Some(name_of_string(String::from("chunked")));
+		let tree: RazTree<usize,Count> = RazTree::from_iter_chunked(data.clone(), &mut Chunker::new(20, base()));
+		assert!(tree.validate().is_empty());
+		assert_eq!(data, tree.clone().fold_lr(Vec::new(), Rc::new(|mut v: Vec<usize>, e: &usize| { v.push(*e); v })));
+
+		// pushing through a raz archives at the same points
+		let mut r: Raz<usize,Count> = Raz::new();
+		let mut chunker = Chunker::new(20, base());
+		for &e in data.iter() { r.push_left_chunked(e, &mut chunker); }
+		assert_eq!(tree, r.unfocus());
+
+		// levels keep it balanced
+		let seq: RazTree<usize,Count> = RazTree::from_iter_chunked(0..100_000, &mut Chunker::new(16, None));
+		assert!(seq.depth() <= 3 * seq.stats().balanced_depth, "depth {}", seq.depth());
+	}
+
+	#[test]
+	fn test_rebalance() {
+		// joining with rising levels makes a tree as deep as it is long
+		let mut tree: RazTree<usize,Count> = RazTree::from_vec((0..10).collect()).unwrap();
+		for i in 1..200 {
+			let next = RazTree::from_vec((i*10..i*10+10).collect()).unwrap();
+			tree = RazTree::join(tree, i as u32, Some(name_of_usize(i)), next).unwrap();
+		}
+		let stats = tree.stats();
+		assert_eq!((200, 2000, 200, 9), (stats.leaves, stats.elements, stats.depth, stats.balanced_depth));
+		assert_eq!(200, tree.depth());
+
+		let names = |t: RazTree<usize,Count>, nm: &str| ns(name_of_string(String::from(nm)),||{ t.fold_lr_meta(
+			Vec::new(),
+			Rc::new(|a,_e:&usize|{a}),
+			Rc::new(|mut a:Vec<Option<Name>>,(_l,n):(_,Option<Name>)|{a.push(n);a}),
+		) });
+		let before = names(tree.clone(), "before");
+		let balanced = tree.rebalance();
+		assert!(balanced.validate().is_empty());
+		assert_eq!(stats.balanced_depth, balanced.depth());
+		assert_eq!(stats.balanced_depth, balanced.stats().balanced_depth);
+		assert_eq!((0..2000).collect::<Vec<_>>(), balanced.clone().fold_lr(Vec::new(), Rc::new(|mut v: Vec<usize>, e: &usize| { v.push(*e); v })));
+		assert_eq!(before, names(balanced, "after"));
+
+		let empty: RazTree<usize,Count> = RazTree::empty();
+		assert_eq!(0, empty.depth());
+		assert!(empty.rebalance().is_empty());
+	}
+
+	#[test]
+	fn test_stats() {
+		let mut r: Raz<usize,Count> = Raz::new();
+		for i in 0..100 {
+			r.push_left(i);
+			if i == 9 || i == 49 { r.archive_left(2, Some(name_of_usize(i))) }
+			if i == 29 { r.archive_left(1, None) }
+		}
+		let tree = r.unfocus();
+		let stats = tree.stats();
+		assert_eq!(TreeStats{
+			leaves: 4, elements: 100, min_leaf: 10, max_leaf: 50,
+			depth: 4, balanced_depth: 3, leaf_depths: vec![0,1,1,2],
+			named: 2, unnamed: 1, bytes: stats.bytes,
+		}, stats);
+		assert_eq!(25.0, stats.avg_leaf());
+		assert!(stats.bytes >= 100 * ::std::mem::size_of::<usize>());
+		assert_eq!(TreeStats::default(), RazTree::<usize,Count>::empty().stats());
+	}
+
+	#[test]
+	fn test_move() {
+		let mut r: Raz<usize,Count> = Raz::new();
+		for i in 0..1000 {
+			r.push_left(i);
+			if i % 10 == 9 { r.archive_left(::inc_level(),Some(name_of_usize(i))) }
+		}
+		let tree = r.unfocus();
+		let names = |t: RazTree<usize,Count>| ns(name_of_string(String::from("names")),||{ t.fold_up_nl(
+			Rc::new(|_:&usize|0), Rc::new(|a,b|a+b),
+			Rc::new(|a,_,n:Option<Name>,b| a + b + if n.is_some() {1} else {0}),
+		).unwrap() });
+		let named = names(tree.clone());
+
+		let mut raz = tree.focus(500usize).unwrap();
+		let mut data = (0..1000).collect::<Vec<_>>();
+		let mut pos = 500;
+		for &(left,k) in &[(true,3),(false,7),(true,250),(false,400),(true,1),(false,10),(true,2000),(false,999),(false,5)] {
+			let moved = if left { raz.move_left(k) } else { raz.move_right(k) };
+			let expected = if left { ::std::cmp::min(k,pos) } else { ::std::cmp::min(k,data.len()-pos) };
+			assert_eq!(expected, moved);
+			if left { pos -= moved } else { pos += moved }
+			assert_eq!(pos, raz.position());
+			assert_eq!(if pos > 0 { Some(data[pos-1]) } else { None }, raz.peek_left());
+			assert_eq!(data.get(pos).cloned(), raz.peek_right());
+			assert!(raz.validate().is_empty());
+		}
+
+		// edits along the way
+		assert!(raz.seek(123));
+		raz.push_left(5000);
+		data.insert(123, 5000);
+		assert!(raz.seek(700));
+		assert_eq!(Some(data[699]), raz.pop_left());
+		data.remove(699);
+		assert!(!raz.seek(2000));
+		assert_eq!(data.len(), raz.position());
+		assert!(raz.seek(0));
+		assert_eq!(Some(5000), { raz.move_right(124); raz.peek_left() });
+
+		let tree = raz.unfocus();
+		assert!(tree.validate().is_empty());
+		assert_eq!(data, tree.clone().fold_lr(Vec::new(), Rc::new(|mut v: Vec<usize>, e: &usize| { v.push(*e); v })));
+		assert_eq!(named, names(tree));
+	}
+
+	#[test]
+	fn test_validate() {
+		let tree = example_tree();
+		assert!(tree.validate().is_empty());
+
+		let mut raz = tree.clone().focus(5usize).unwrap();
+		assert!(raz.validate().is_empty());
+		for _ in 0..4 { raz.pop_left(); raz.pop_right(); }
+		assert!(raz.validate().is_empty());
+		raz.push_left(100);
+		raz.archive_left(3,Some(name_of_usize(100)));
+		raz.push_left(101);
+		assert!(raz.unfocus().validate().is_empty());
+
+		// stale meta data
+		let mut bad = tree.clone();
+		bad.meta = Count(3);
+		assert_eq!(vec![Invalid::Meta{level: 0, name: None}], bad.validate());
+		let bad: RazTree<_,Count> = RazTree::join(
+			RazTree::from_vec(vec![1,2]).unwrap(), 4, Some(name_of_usize(4)),
+			RazTree{meta: Count(0), tree: Some(leaf(vec![],None))},
+		).unwrap();
+		assert_eq!(vec![Invalid::EmptyLeaf], bad.validate());
+		let bad: RazTree<_,Count> = RazTree{meta: Count(2), tree: Some(
+			ltree::Tree::new(2,None,TreeData::Branch(Count(2),Count(5)),
+				Some(leaf(vec![1,2],None)), Some(leaf(vec![3,4],None)),
+			).unwrap()
+		)};
+		assert_eq!(vec![Invalid::Meta{level: 2, name: None}, Invalid::Meta{level: 0, name: None}], bad.validate());
+	}
+
+	/// operations for model-based testing against a `Vec`
+	#[derive(Debug,Clone)]
+	enum Op {
+		PushLeft(usize),
+		PushRight(usize),
+		PopLeft,
+		PopRight,
+		ArchiveLeft(u32),
+		ArchiveRight(u32),
+		// unfocus, then focus on (index % (len+1))
+		Refocus(usize),
+		// unfocus, then join with new data after (true) or before the tree
+		Join(Vec<usize>,bool),
+	}
+
+	fn gen_ops<R: ::rand::Rng>(rng: &mut R, count: usize) -> Vec<Op> {
+		(0..count).map(|_| match rng.gen_range(0,16) {
+			0..=3 => Op::PushLeft(rng.gen_range(0,1000)),
+			4..=6 => Op::PushRight(rng.gen_range(0,1000)),
+			7..=8 => Op::PopLeft,
+			9..=10 => Op::PopRight,
+			11 => Op::ArchiveLeft(tree::gen_level(rng)),
+			12 => Op::ArchiveRight(tree::gen_level(rng)),
+			13..=14 => Op::Refocus(rng.gen()),
+			_ => {
+				let len = rng.gen_range(1,5);
+				Op::Join((0..len).map(|_|rng.gen_range(0,1000)).collect(), rng.gen())
+			},
+		}).collect()
+	}
+
+	fn check_tree(tree: &RazTree<usize,Count>, model: &Vec<usize>) -> Result<(),String> {
+		let contents = tree.clone().fold_lr(Vec::new(), Rc::new(|mut v: Vec<usize>, e: &usize| { v.push(*e); v }));
+		if &contents != model { return Err(format!("contents {:?}, expected {:?}", contents, model)) }
+		if tree.meta() != &Count(model.len()) { return Err(format!("meta {:?}, expected {:?}", tree.meta(), model.len())) }
+		if let Some(ref t) = tree.tree {
+			if !good_levels(t) { return Err(String::from("bad levels")) }
+		}
+		let errors = tree.validate();
+		if !errors.is_empty() { return Err(format!("invalid tree: {:?}", errors)) }
+		Ok(())
+	}
+
+	/// runs the operations on a `Raz` and a `Vec`, comparing them after each step
+	fn run_model(ops: &[Op]) -> Result<(),String> {
+		let mut raz: Raz<usize,Count> = Raz::new();
+		let mut model: Vec<usize> = Vec::new();
+		let mut pos = 0;
+		let mut names = 0;
+		for (step, op) in ops.iter().enumerate() {
+			let result = (|| {
+				match *op {
+					Op::PushLeft(e) => { raz.push_left(e); model.insert(pos,e); pos += 1; },
+					Op::PushRight(e) => { raz.push_right(e); model.insert(pos,e); },
+					Op::PopLeft => {
+						let expected = if pos > 0 { pos -= 1; Some(model.remove(pos)) } else { None };
+						let popped = raz.pop_left();
+						if popped != expected { return Err(format!("popped {:?}, expected {:?}", popped, expected)) }
+					},
+					Op::PopRight => {
+						let expected = if pos < model.len() { Some(model.remove(pos)) } else { None };
+						let popped = raz.pop_right();
+						if popped != expected { return Err(format!("popped {:?}, expected {:?}", popped, expected)) }
+					},
+					Op::ArchiveLeft(lev) => { names += 1; raz.archive_left(lev, Some(name_of_usize(names))); },
+					Op::ArchiveRight(lev) => { names += 1; raz.archive_right(lev, Some(name_of_usize(names))); },
+					Op::Refocus(i) => {
+						let tree = raz.clone().unfocus();
+						check_tree(&tree, &model)?;
+						pos = i % (model.len() + 1);
+						raz = tree.focus(pos).ok_or(String::from("focus failed"))?;
+					},
+					Op::Join(ref data, after) => {
+						let tree = raz.clone().unfocus();
+						check_tree(&tree, &model)?;
+						let other = RazTree::from_vec(data.clone()).unwrap();
+						let (l,r) = if after { (tree, other) } else { (other, tree) };
+						let top = |t: &RazTree<usize,Count>| t.tree.as_ref().map_or(0, |t| t.level());
+						let level = ::std::cmp::max(top(&l) + 1, top(&r));
+						names += 1;
+						let joined = match RazTree::join(l.clone(), level, Some(name_of_usize(names)), r.clone()) {
+							Some(t) => t,
+							None => if l.is_empty() { r } else { l },
+						};
+						if after { model.extend(data.iter().cloned()) } else {
+							model = data.iter().chain(model.iter()).cloned().collect();
+							pos += data.len();
+						}
+						check_tree(&joined, &model)?;
+						raz = joined.focus(pos).ok_or(String::from("focus failed"))?;
+					},
+				}
+				let errors = raz.validate();
+				if !errors.is_empty() { return Err(format!("invalid raz: {:?}", errors)) }
+				let (left, right) = (raz.peek_left(), raz.peek_right());
+				let expected = (
+					if pos > 0 { Some(model[pos-1]) } else { None },
+					model.get(pos).cloned(),
+				);
+				if (left, right) != expected { return Err(format!("peeked {:?}, expected {:?}", (left, right), expected)) }
+				check_tree(&raz.clone().unfocus(), &model)
+			})();
+			if let Err(msg) = result { return Err(format!("step {} {:?}: {}", step, op, msg)) }
+		}
+		Ok(())
+	}
+
+	fn run_caught(ops: &[Op]) -> Result<(),String> {
+		use std::panic::{catch_unwind,AssertUnwindSafe};
+		match catch_unwind(AssertUnwindSafe(|| run_model(ops))) {
+			Ok(result) => result,
+			Err(_) => Err(String::from("panicked")),
+		}
+	}
+
+	/// removes chunks of operations, then single operations,
+	/// as long as the trace still fails
+	fn shrink(mut ops: Vec<Op>) -> Vec<Op> {
+		let mut chunk = ops.len() / 2;
+		while chunk > 0 {
+			let mut i = 0;
+			while i + chunk <= ops.len() {
+				let mut smaller = ops.clone();
+				smaller.drain(i..i+chunk);
+				if run_caught(&smaller).is_err() { ops = smaller } else { i += 1 }
+			}
+			chunk /= 2;
+		}
+		ops
+	}
+
+	#[test]
+	fn test_model() {
+		use rand::{StdRng,SeedableRng};
+		let mut rng = StdRng::from_seed(&[4,9,16,25][..]);
+		for trial in 0..200 {
+			let ops = gen_ops(&mut rng, 60);
+			if run_caught(&ops).is_err() {
+				let ops = shrink(ops);
+				panic!("trial {} failed: {}\nshrunk trace: {:?}", trial, run_caught(&ops).unwrap_err(), ops);
+			}
+		}
+	}
+
+	#[test]
+	fn test_freeze_thaw() {
+		use std::thread;
+		let tree = example_tree();
+		let frozen = tree.freeze();
+		assert_eq!(12, frozen.len());
+		assert_eq!(5, frozen.level());
+		assert_eq!(Some(&1), frozen.get(0));
+		assert_eq!(Some(&8), frozen.get(7));
+		assert_eq!(None, frozen.get(12));
+		assert_eq!((1..13).collect::<Vec<_>>(), frozen.iter().cloned().collect::<Vec<_>>());
+
+		let sum = tree.clone().fold_up(Rc::new(|e:&usize|*e),Rc::new(|a,b|a+b));
+		assert_eq!(sum, frozen.fold_up(&|e:&usize|*e, &|a,b|a+b));
+		let worker = frozen.clone();
+		let handle = thread::spawn(move || worker.iter().map(|e| e*2).sum::<usize>());
+		assert_eq!(156, handle.join().unwrap());
+
+		let thawed: RazTree<usize,Count> = RazTree::thaw(&frozen, Some(name_of_string(String::from("thaw"))));
+		assert!(thawed.validate().is_empty());
+		assert_eq!(tree.meta(), thawed.meta());
+		let levels = |t: RazTree<usize,Count>| t.fold_lr_meta(Vec::new(),
+			Rc::new(|mut v: Vec<u32>, e: &usize| { v.push(*e as u32); v }),
+			Rc::new(|mut v: Vec<u32>, (l,_)| { v.push(100 + l); v }),
+		);
+		assert_eq!(levels(tree), levels(thawed));
+		assert!(RazTree::<usize,Count>::thaw(&FrozenSeq::empty(), None).is_empty());
+	}
+
+	#[test]
+	fn test_flat_map() {
+		let tree = example_tree();
+		// odd numbers are dropped, even ones doubled
+		let fm: RazTree<usize,Count> = ns(name_of_string(String::from("flat_map")),||{
+			tree.clone().flat_map(Rc::new(|e:&usize| if *e % 2 == 1 { vec!() } else { vec!(*e,*e) }))
+		});
+		assert!(fm.validate().is_empty());
+		assert_eq!(Count(12), *fm.meta());
+		let contents = fm.clone().fold_lr(Vec::new(), Rc::new(|mut v: Vec<usize>, e: &usize| { v.push(*e); v }));
+		assert_eq!(vec!(2,2,4,4,6,6,8,8,10,10,12,12), contents);
+		let r = fm.focus(4usize).unwrap();
+		assert_eq!(Some(4), r.peek_left());
+		assert_eq!(Some(6), r.peek_right());
+
+		// leaves that map to nothing are removed
+		let mut raz: Raz<usize,Count> = Raz::new();
+		for i in 0..100 {
+			raz.push_left(i);
+			if i % 10 == 9 { raz.archive_left(::inc_level(),Some(name_of_usize(i))) }
+		}
+		let fm: RazTree<usize,Count> = ns(name_of_string(String::from("flat_map_sparse")),||{
+			raz.unfocus().flat_map(Rc::new(|e:&usize| if *e >= 25 && *e < 75 { vec!() } else { vec!(*e) }))
+		});
+		assert!(fm.validate().is_empty());
+		assert_eq!(Count(50), *fm.meta());
+		let none: RazTree<usize,Count> = example_tree().flat_map(Rc::new(|_:&usize| Vec::<usize>::new()));
+		assert!(none.is_empty());
+	}
+
+	#[test]
+	fn test_zip_with() {
+		let tree = example_tree();
+		let mut raz: Raz<usize,Count> = Raz::new();
+		for i in 0..20 {
+			raz.push_left(i * 10);
+			if i % 3 == 2 { raz.archive_left(::inc_level(),Some(name_of_usize(i))) }
+		}
+		let other = raz.unfocus();
+		let contents = |t: RazTree<usize,Count>| t.fold_lr(Vec::new(), Rc::new(|mut v: Vec<usize>, e: &usize| { v.push(*e); v }));
+
+		let sums: RazTree<usize,Count> = ns(name_of_string(String::from("zip_with")),||{
+			tree.clone().zip_with(other.clone(), Rc::new(|a:&usize,b:&usize|a+b))
+		});
+		assert!(sums.validate().is_empty());
+		assert_eq!(Count(12), *sums.meta());
+		assert_eq!((0..12).map(|i|i+1+i*10).collect::<Vec<_>>(), contents(sums));
+
+		// truncated to the shorter sequence, from either side
+		let short: RazTree<usize,Count> = ns(name_of_string(String::from("zip_with_short")),||{
+			other.clone().zip_with(RazTree::from_vec(vec!(1,2,3,4,5)).unwrap(), Rc::new(|a:&usize,b:&usize|a+b))
+		});
+		assert!(short.validate().is_empty());
+		assert_eq!(vec!(1,12,23,34,45), contents(short));
+		let short: RazTree<usize,Count> = ns(name_of_string(String::from("zip_with_long")),||{
+			RazTree::from_vec(vec!(1,2,3,4,5)).unwrap().zip_with(other.clone(), Rc::new(|a:&usize,b:&usize|a+b))
+		});
+		assert_eq!(vec!(1,12,23,34,45), contents(short));
+
+		let pairs: RazTree<(usize,usize),Count> = ns(name_of_string(String::from("zip")),||{
+			other.clone().zip(tree.clone())
+		});
+		assert_eq!(Count(12), *pairs.meta());
+		assert!(tree.clone().zip::<usize,Count>(RazTree::empty()).is_empty());
+		assert!(RazTree::<usize,Count>::empty().zip::<usize,Count>(tree).is_empty());
+	}
+
+	#[test]
+	fn test_distinct() {
+		use std::collections::HashSet;
+		let mut r: Raz<usize,Count> = Raz::new();
+		for i in 0..1000 {
+			r.push_left(i % 300);
+			if i % 10 == 9 { r.archive_left(::inc_level(),Some(name_of_usize(i))) }
+		}
+		let tree = r.unfocus();
+		let set = ns(name_of_string(String::from("set1")),||{ tree.clone().distinct() });
+		assert_eq!(300, set.len());
+		assert!(set.contains(&0) && set.contains(&299) && !set.contains(&300));
+		assert_eq!((0..300).collect::<HashSet<_>>(), set.to_vec().into_iter().map(|(e,_)|e).collect());
+
+		let mut r = tree.focus(500usize).unwrap();
+		r.push_left(1000);
+		r.push_right(250);
+		let tree = r.unfocus();
+		assert_eq!(301, ns(name_of_string(String::from("set2")),||{ tree.clone().distinct_count() }));
+		assert_eq!(0, RazTree::<usize,Count>::empty().distinct_count());
+	}
+
+	#[test]
+	fn test_group_by() {
+		let mut r: Raz<usize,Count> = Raz::new();
+		for i in 0..1000 {
+			r.push_left(i);
+			if i % 10 == 9 { r.archive_left(::inc_level(),Some(name_of_usize(i))) }
+		}
+		let tree = r.unfocus();
+		let contents = |t: RazTree<usize,Count>| t.fold_lr(Vec::new(), Rc::new(|mut v: Vec<usize>, e: &usize| { v.push(*e); v }));
+
+		let hist = ns(name_of_string(String::from("hist")),||{ tree.clone().histogram(Rc::new(|e:&usize| e % 7)) });
+		assert_eq!(7, hist.len());
+		assert_eq!(Some(143), hist.get(&0));
+		assert_eq!(Some(142), hist.get(&6));
+		assert_eq!(None, hist.get(&7));
+
+		let groups = ns(name_of_string(String::from("groups")),||{ tree.clone().group_by(Rc::new(|e:&usize| e % 7)) });
+		assert_eq!(7, groups.len());
+		for k in 0..7 {
+			let group = groups.get(&k).unwrap();
+			assert!(group.validate().is_empty());
+			assert_eq!((0..1000).filter(|e| e % 7 == k).collect::<Vec<_>>(), contents(group));
+		}
+
+		let mut r = tree.focus(500usize).unwrap();
+		r.push_left(7000);
+		r.push_left(7001);
+		let tree = r.unfocus();
+		let hist = ns(name_of_string(String::from("hist2")),||{ tree.clone().histogram(Rc::new(|e:&usize| e % 7)) });
+		assert_eq!(Some(144), hist.get(&0));
+		assert_eq!(Some(144), hist.get(&1));
+		assert!(RazTree::<usize,Count>::empty().histogram(Rc::new(|e:&usize| *e)).is_empty());
+	}
+
+	#[test]
+	fn test_max_min() {
+		let data = vec!(5,3,9,1,9,0,7,2,8,1,0,6);
+		let mut r: Raz<usize,CountMax<usize>> = Raz::new();
+		for (i,e) in data.iter().enumerate() {
+			r.push_left(*e);
+			if i % 3 == 2 { r.archive_left(::inc_level(),Some(name_of_usize(i))) }
+		}
+		let tree = r.unfocus();
+		assert_eq!(Some(&9), tree.max());
+		assert_eq!(Some(2), tree.argmax());
+		let r = tree.clone().focus(tree.argmax().unwrap()).unwrap();
+		assert_eq!(Some(9), r.peek_right());
+
+		// edits update the position without a fold
+		let mut r = tree.clone().focus(4usize).unwrap();
+		r.push_left(20);
+		let tree2 = r.unfocus();
+		assert_eq!(Some(&20), tree2.max());
+		assert_eq!(Some(4), tree2.argmax());
+		assert_eq!(CountMax{count: 0, max: None}, *RazTree::<usize,CountMax<usize>>::empty().meta());
+
+		// by the second field, with ties
+		let pairs: RazTree<(usize,usize),Count> = tree.map(Rc::new(|e:&usize|(*e, e % 3)));
+		let by_snd = |a:&(usize,usize),b:&(usize,usize)| a.1.cmp(&b.1);
+		assert_eq!(Some((8,2)), pairs.clone().max_by(Rc::new(by_snd)));
+		assert_eq!(Some((3,0)), pairs.clone().min_by(Rc::new(by_snd)));
+		assert_eq!(None, RazTree::<usize,Count>::empty().max_by(Rc::new(|a:&usize,b:&usize|a.cmp(b))));
+	}
+
+	#[test]
+	fn test_windows() {
+		let mut r: Raz<usize,Count> = Raz::new();
+		for i in 0..200 {
+			r.push_left(i * i % 17);
+			if i % 4 == 3 { r.archive_left(::inc_level(),Some(name_of_usize(i))) }
+		}
+		let tree = r.unfocus();
+		let data = tree.clone().fold_lr(Vec::new(), Rc::new(|mut v: Vec<usize>, e: &usize| { v.push(*e); v }));
+		let contents = |t: RazTree<usize,Count>| t.fold_lr(Vec::new(), Rc::new(|mut v: Vec<usize>, e: &usize| { v.push(*e); v }));
+		let sum = |w: &[usize]| w.iter().sum::<usize>();
+
+		for &k in &[1,2,5,9,200] {
+			let sums: RazTree<usize,Count> = ns(name_of_usize(k),||{ tree.clone().windows(k, Rc::new(sum)) });
+			assert!(sums.validate().is_empty());
+			assert_eq!(data.windows(k).map(sum).collect::<Vec<_>>(), contents(sums));
+		}
+		let none: RazTree<usize,Count> = tree.clone().windows(201, Rc::new(sum));
+		assert!(none.is_empty());
+
+		// after an edit
+		let mut r = tree.focus(100usize).unwrap();
+		r.push_left(1000);
+		let tree = r.unfocus();
+		let mut data = data;
+		data.insert(100, 1000);
+		let sums: RazTree<usize,Count> = ns(name_of_string(String::from("edit")),||{ tree.windows(5, Rc::new(sum)) });
+		assert_eq!(data.windows(5).map(sum).collect::<Vec<_>>(), contents(sums));
+	}
+
+	#[test]
+	fn test_windows_sizes() {
+		::adapton::engine::manage::init_dcg();
+		let mut r: Raz<usize,Count> = Raz::new();
+		for i in 0..40 {
+			r.push_left(i);
+			if i % 4 == 3 { r.archive_left(::inc_level(),Some(name_of_usize(i))) }
+		}
+		let tree = r.unfocus();
+		let data = (0..40).collect::<Vec<usize>>();
+		let contents = |t: RazTree<usize,Count>| t.fold_lr(Vec::new(), Rc::new(|mut v: Vec<usize>, e: &usize| { v.push(*e); v }));
+		let sum = |w: &[usize]| w.iter().sum::<usize>();
+		// the same tree, in the same namespace, with two sizes
+		let twos: RazTree<usize,Count> = tree.clone().windows(2, Rc::new(sum));
+		let threes: RazTree<usize,Count> = tree.clone().windows(3, Rc::new(sum));
+		assert_eq!(data.windows(2).map(sum).collect::<Vec<_>>(), ns(name_of_usize(2),||{ contents(twos) }));
+		assert_eq!(data.windows(3).map(sum).collect::<Vec<_>>(), ns(name_of_usize(3),||{ contents(threes) }));
+	}
+
+	#[test]
+	fn test_to_string_seps() {
+		::adapton::engine::manage::init_dcg();
+		let mut r: Raz<usize,Count> = Raz::new();
+		for i in 0..40 {
+			r.push_left(i);
+			if i % 4 == 3 { r.archive_left(::inc_level(),Some(name_of_usize(i))) }
+		}
+		let tree = r.unfocus();
+		let expect = |sep: &str| (0..40).map(|i| i.to_string()).collect::<Vec<_>>().join(sep);
+		assert_eq!(expect(","), tree.clone().to_string_incremental(",").to_string());
+		assert_eq!(expect(";"), tree.clone().to_string_incremental(";").to_string());
+	}
+
+	#[test]
+	fn test_to_string() {
+		let tree = example_tree();
+		let text = ns(name_of_string(String::from("text")),||{ tree.clone().to_string_incremental(", ") });
+		assert_eq!("1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12", text.to_string());
+		assert_eq!(text.to_string().len(), text.len());
+
+		let mut r = tree.focus(5usize).unwrap();
+		r.push_left(50);
+		let text = ns(name_of_string(String::from("text2")),||{ r.unfocus().to_string_incremental("") });
+		assert_eq!("12345506789101112", text.to_string());
+		assert_eq!(17, text.len());
+		assert!(RazTree::<usize,Count>::empty().to_string_incremental(",").is_empty());
+
+		let words: RazTree<String,Count> = example_tree().map(Rc::new(|e:&usize| if e % 2 == 1 { String::new() } else { String::from("ab") }));
+		let joined = ns(name_of_string(String::from("concat")),||{ words.concat() });
+		assert_eq!("abababababab", joined.to_string());
+	}
+
+	#[test]
+	fn test_reverse() {
+		let tree = example_tree();
+		let rev = ns(name_of_string(String::from("rev")),||{ tree.clone().reverse() });
+		assert!(rev.validate().is_empty());
+		assert_eq!(tree.meta(), rev.meta());
+		let contents = |t: RazTree<usize,Count>| t.fold_lr(Vec::new(), Rc::new(|mut v: Vec<usize>, e: &usize| { v.push(*e); v }));
+		assert_eq!((1..13).rev().collect::<Vec<_>>(), contents(rev.clone()));
+
+		let levels = |t: RazTree<usize,Count>| t.fold_lr_meta(Vec::new(),
+			Rc::new(|mut v: Vec<u32>, e: &usize| { v.push(*e as u32); v }),
+			Rc::new(|mut v: Vec<u32>, (l,_)| { v.push(100 + l); v }),
+		);
+		let mut rev_levels = levels(tree.clone());
+		rev_levels.reverse();
+		assert_eq!(rev_levels, levels(rev.clone()));
+
+		let back = ns(name_of_string(String::from("back")),||{ rev.clone().reverse() });
+		assert!(back.validate().is_empty());
+		assert_eq!(levels(tree), levels(back));
+
+		// the reversed tree can be edited
+		let mut r = rev.focus(3usize).unwrap();
+		assert_eq!(Some(10), r.peek_left());
+		r.push_left(50);
+		assert_eq!(13, r.unfocus().meta().0);
+		assert!(RazTree::<usize,Count>::empty().reverse().is_empty());
+	}
+
+	#[test]
+	fn test_reverse_random() {
+		use rand::{StdRng,SeedableRng,Rng};
+		let mut rng = StdRng::from_seed(&[1,2,3][..]);
+		let mut r: Raz<usize,Count> = Raz::new();
+		for i in 0..2000 {
+			r.push_left(i);
+			if rng.gen::<u32>() % 7 == 0 {
+				let level = ::level_tree::gen_branch_level(&mut rng);
+				let name = if rng.gen() { Some(name_of_usize(i)) } else { None };
+				r.archive_left(level,name);
+			}
+		}
+		let tree = r.unfocus();
+		let rev = ns(name_of_string(String::from("rev")),||{ tree.clone().reverse() });
+		assert!(rev.validate().is_empty());
+		let contents = |t: RazTree<usize,Count>| t.fold_lr(Vec::new(), Rc::new(|mut v: Vec<usize>, e: &usize| { v.push(*e); v }));
+		assert_eq!((0..2000).rev().collect::<Vec<_>>(), contents(rev.clone()));
+		let back = ns(name_of_string(String::from("back")),||{ rev.reverse() });
+		assert!(back.validate().is_empty());
+		assert_eq!(contents(tree), contents(back));
+	}
+
+	#[test]
+	fn test_par_fold_up() {
+		use std::sync::Arc;
+		let mut r: Raz<usize,()> = Raz::new();
+		for i in 0..10000 {
+			r.push_left(i);
+			if i % 10 == 0 {
+				r.archive_left(::inc_level(),Some(name_of_usize(i)));
+			}
+		}
+		let tree = r.unfocus();
+		let frozen = tree.freeze();
+		// non-commutative, to check that branches combine in order
+		let seq = tree.clone().fold_up(Rc::new(|e:&usize|vec!(*e)),Rc::new(|mut a:Vec<usize>,b|{a.extend(b);a}));
+		for &threads in &[0,1,2,3,8] {
+			let par = frozen.par_fold_up(threads, Arc::new(|e:&usize|vec!(*e)), Arc::new(|mut a:Vec<usize>,b|{a.extend(b);a}));
+			assert_eq!(seq, par);
+		}
+		let sum = tree.fold_up(Rc::new(|e:&usize|*e),Rc::new(|a,b|a+b));
+		assert_eq!(sum, frozen.par_fold_up(4, Arc::new(|e:&usize|*e), Arc::new(|a,b|a+b)));
+		assert_eq!(None, FrozenSeq::<usize>::empty().par_fold_up(4, Arc::new(|e:&usize|*e), Arc::new(|a,b|a+b)));
+	}
 }
@@ -4,8 +4,8 @@
 
 use std::fmt::Debug;
 use std::hash::{Hash,Hasher};
-use std::collections::HashMap;
 use adapton::engine::Name;
+use trie::{Trie,FinSet};
 
 /// trait for creating and searching for meta data in the
 /// branches of the raz.
@@ -141,49 +141,138 @@ impl<E> RazMeta<E> for Count {
 	}	
 }
 
+/// Meta data for element count and the position of the maximum element
+///
+/// Positions by count like `Count`, and keeps the greatest element of
+/// each subtree with its index, so that the maximum of a whole tree and
+/// its index are available without a fold. The first of equal elements
+/// is kept.
+#[derive(Clone,Eq,PartialEq,Hash,Debug)]
+pub struct CountMax<E> {
+	pub count: usize,
+	/// the greatest element and its index within the subtree
+	pub max: Option<(E,usize)>,
+}
+
+impl<E: Debug+Clone+Eq+Hash+Ord> RazMeta<E> for CountMax<E> {
+	type Index = usize;
+
+	fn from_none(_lev: u32, _n: Option<Name>) -> Self { CountMax{count: 0, max: None} }
+	fn from_vec(vec: &Vec<E>, _lev: u32, _n: Option<Name>) -> Self {
+		let mut max: Option<(&E,usize)> = None;
+		for (i,e) in vec.iter().enumerate() {
+			match max {
+				Some((m,_)) if m >= e => {},
+				_ => max = Some((e,i)),
+			}
+		}
+		CountMax{count: vec.len(), max: max.map(|(e,i)|(e.clone(),i))}
+	}
+	fn from_meta(l: &Self, r: &Self, _lev: u32, _n: Option<Name>) -> Self {
+		let max = match (l.max.as_ref(), r.max.as_ref()) {
+			(None, None) => None,
+			(Some(lm), None) => Some(lm.clone()),
+			(None, Some(rm)) => Some((rm.0.clone(), rm.1 + l.count)),
+			(Some(lm), Some(rm)) => {
+				if rm.0 > lm.0 { Some((rm.0.clone(), rm.1 + l.count)) } else { Some(lm.clone()) }
+			},
+		};
+		CountMax{count: l.count + r.count, max}
+	}
+	fn navigate(l: &Self, r: &Self, index: &Self::Index) -> Navigation<Self::Index> {
+		<Count as RazMeta<E>>::navigate(&Count(l.count), &Count(r.count), index)
+	}
+	/// # Panics
+	/// Panics if the index is too high
+	fn split_vec<'a>(vec: &'a Vec<E>, index: &Self::Index) -> (&'a [E],&'a [E]) {
+		<Count as RazMeta<E>>::split_vec(vec, index)
+	}
+}
+
 impl FirstLast for usize {
 	fn first() -> Self { 0 }
 	fn last() -> Self { usize::max_value() }
 }
 
-/// Metadata for names in a raz tree.
+/// Metadata for names and element count in a raz tree.
 ///
-/// Hash is implemented by a no-op, since the data here
-/// can be found elsewhere in the tree. This is not intended
+/// The names are kept in a persistent hash trie, so the names of a
+/// branch share most of their structure with those of its children.
+/// Building them costs time in the number of names of the smaller
+/// child, rather than copying both; it is not logarithmic.
+///
+/// Hashing and equality use the count and the hash cached at the
+/// root of the trie, so they take constant time. This is not intended
 /// to be used outside of a raz.
-#[derive(Clone,Eq,PartialEq,Debug)]
-pub struct Names(pub HashMap<Name,()>);
+#[derive(Clone,Eq,Debug)]
+pub struct Names{
+	count: usize,
+	names: FinSet<Name>,
+}
 impl Hash for Names {
-	/// does nothing
-	fn hash<H:Hasher>(&self, _state: &mut H) {}
+	/// hashes the count and the hash of the names
+	fn hash<H:Hasher>(&self, state: &mut H) {
+		self.count.hash(state);
+		self.names.hash_val().hash(state)
+	}
+}
+impl PartialEq for Names {
+	/// compares the counts and the hashes of the names
+	fn eq(&self, other: &Self) -> bool {
+		self.count == other.count &&
+		self.names.len() == other.names.len() &&
+		self.names.hash_val() == other.names.hash_val()
+	}
+}
+
+impl Names {
+	/// number of elements in the branch
+	pub fn count(&self) -> usize { self.count }
+	/// whether the name is in the branch
+	pub fn contains(&self, name: &Name) -> bool { self.names.contains(name) }
+	/// number of names in the branch
+	pub fn len(&self) -> usize { self.names.len() }
+	/// true if there are no names in the branch
+	pub fn is_empty(&self) -> bool { self.names.is_empty() }
+	/// the names in the branch, in no particular order
+	pub fn names(&self) -> Vec<Name> { self.names.to_vec().into_iter().map(|(n,_)| n).collect() }
+	/// the set of names in the branch
+	pub fn set(&self) -> &FinSet<Name> { &self.names }
+
+	fn with_name(count: usize, n: Option<Name>) -> Self {
+		Names{count, names: single(n)}
+	}
+}
+
+// a set of at most one name
+fn single(n: Option<Name>) -> FinSet<Name> {
+	match n {
+		None => Trie::empty(),
+		Some(nm) => Trie::from_hashmap(Some((nm,())).into_iter().collect()),
+	}
 }
 
 impl<E> RazMeta<E> for Names {
 	type Index = Position<Name>;
 
 	fn from_none(_lev: u32, n: Option<Name>) -> Self {
-		let mut h = HashMap::new();
-		match n {None=>{},Some(nm)=>{ h.insert(nm,()); }}
-		Names(h)
+		Names::with_name(0, n)
 	}
-	fn from_vec(_vec: &Vec<E>, _lev: u32, n: Option<Name>) -> Self {
-		let mut h = HashMap::new();
-		match n {None=>{},Some(nm)=>{ h.insert(nm,()); }}
-		Names(h)
+	fn from_vec(vec: &Vec<E>, _lev: u32, n: Option<Name>) -> Self {
+		Names::with_name(vec.len(), n)
 	}
 	fn from_meta(l: &Self, r: &Self, _lev: u32, n: Option<Name>) -> Self {
-		let mut h = HashMap::new();
-		for k in l.0.keys() { h.insert(k.clone(),()); }
-		for k in r.0.keys() { h.insert(k.clone(),()); }
-		match n {None=>{},Some(nm)=>{ h.insert(nm,()); }}
-		Names(h)
+		// the same name is given for both sides of a node, so
+		// the unions are not named, to keep them apart
+		let names = Trie::union(l.names.clone(), r.names.clone(), None);
+		Names{count: l.count + r.count, names: Trie::union(names, single(n), None)}
 	}
 	fn navigate(l: &Self, r: &Self, index: &Self::Index) -> Navigation<Self::Index> {
 		match *index {
 			Position::Left => Navigation::Left(Position::Left),
 			Position::Right => Navigation::Right(Position::Right),
 			Position::Center(ref nm) => {
-				match (l.0.contains_key(nm),r.0.contains_key(nm)) {
+				match (l.contains(nm),r.contains(nm)) {
 					(true,true) => Navigation::Here,
 					(true,false) => Navigation::Left(index.clone()),
 					(false,true) => Navigation::Right(index.clone()),
@@ -192,13 +281,12 @@ impl<E> RazMeta<E> for Names {
 			}
 		}
 	}
-	/// # Panics
-	/// Panics if a name is given as index 
+	/// A name reaches a leaf only through a branch missing its
+	/// other child. The leaf is split at its end.
 	fn split_vec<'a>(vec: &'a Vec<E>, index: &Self::Index) -> (&'a [E],&'a [E]) {
 		match *index {
 			Position::Left => vec.split_at(0),
-			Position::Right => vec.split_at(vec.len()),
-			_ => panic!("There are no names in this region")
+			Position::Right | Position::Center(_) => vec.split_at(vec.len()),
 		}
 	}
 }
\ No newline at end of file
@@ -0,0 +1,132 @@
+//! Ropes of Shared Strings
+//!
+//! An immutable string built from shared pieces
+//!
+//! - concatenation in O(1), sharing both sides
+//! - pieces are `Rc<str>`, so results of incremental folds
+//!   can be reused without copying text
+//! - `Display` writes the pieces in order
+//!
+//! The shape of a rope follows the order of concatenation, so
+//! ropes built by folding a level tree are balanced.
+
+use std::fmt;
+use std::hash::{Hash,Hasher};
+use std::rc::Rc;
+
+/// Immutable string of shared pieces
+#[derive(Clone,Debug)]
+pub enum Rope {
+	Empty,
+	Leaf(Rc<str>),
+	Concat{
+		len: usize,
+		left: Rc<Rope>,
+		right: Rc<Rope>,
+	},
+}
+
+impl Rope {
+	/// a rope with no text
+	pub fn empty() -> Self { Rope::Empty }
+
+	/// the length of the text, in bytes
+	pub fn len(&self) -> usize {
+		match *self {
+			Rope::Empty => 0,
+			Rope::Leaf(ref s) => s.len(),
+			Rope::Concat{len, ..} => len,
+		}
+	}
+
+	pub fn is_empty(&self) -> bool { self.len() == 0 }
+
+	/// joins two ropes, sharing both
+	pub fn concat(left: Self, right: Self) -> Self {
+		if left.is_empty() { return right }
+		if right.is_empty() { return left }
+		Rope::Concat{
+			len: left.len() + right.len(),
+			left: Rc::new(left),
+			right: Rc::new(right),
+		}
+	}
+}
+
+/// Ropes are equal if they have the same pieces in the same shape.
+/// Shared pieces are compared by pointer.
+impl PartialEq for Rope {
+	fn eq(&self, other: &Self) -> bool {
+		match *self {
+			Rope::Empty => matches!(*other, Rope::Empty),
+			Rope::Leaf(ref a) => match *other {
+				Rope::Leaf(ref b) => Rc::ptr_eq(a, b) || a == b,
+				_ => false,
+			},
+			Rope::Concat{len, left: ref l1, right: ref r1} => match *other {
+				Rope::Concat{len: len2, left: ref l2, right: ref r2} => {
+					len == len2
+					&& (Rc::ptr_eq(l1, l2) || l1 == l2)
+					&& (Rc::ptr_eq(r1, r2) || r1 == r2)
+				},
+				_ => false,
+			},
+		}
+	}
+}
+impl Eq for Rope {}
+
+impl Hash for Rope {
+	fn hash<H:Hasher>(&self, state: &mut H) {
+		match *self {
+			Rope::Empty => {},
+			Rope::Leaf(ref s) => s.hash(state),
+			Rope::Concat{len, ref left, ref right} => {
+				len.hash(state);
+				left.hash(state);
+				right.hash(state);
+			},
+		}
+	}
+}
+
+impl fmt::Display for Rope {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match *self {
+			Rope::Empty => Ok(()),
+			Rope::Leaf(ref s) => f.write_str(s),
+			Rope::Concat{ref left, ref right, ..} => {
+				left.fmt(f)?;
+				right.fmt(f)
+			},
+		}
+	}
+}
+
+impl From<String> for Rope {
+	fn from(s: String) -> Self {
+		if s.is_empty() { Rope::Empty } else { Rope::Leaf(Rc::from(s)) }
+	}
+}
+
+impl<'a> From<&'a str> for Rope {
+	fn from(s: &'a str) -> Self {
+		if s.is_empty() { Rope::Empty } else { Rope::Leaf(Rc::from(s)) }
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_concat() {
+		let hello = Rope::from("hello");
+		let rope = Rope::concat(Rope::concat(hello.clone(), Rope::from(", ")), Rope::from(String::from("world")));
+		assert_eq!(12, rope.len());
+		assert_eq!("hello, world", rope.to_string());
+		assert_eq!(hello, Rope::concat(Rope::empty(), Rope::concat(hello.clone(), Rope::from(""))));
+		assert!(Rope::concat(Rope::empty(), Rope::empty()).is_empty());
+		assert!(rope != hello);
+	}
+}
@@ -9,7 +9,8 @@ use std::fmt;
 use std::fmt::Debug;
 use std::hash::{Hash,Hasher};
 use std::collections::hash_map::{DefaultHasher};
-use adapton::engine::{cell,force_map,Art,Name,name_pair,name_of_usize};
+use adapton::engine::{Name,name_pair,name_of_usize};
+use art::{cell,force_map,Art};
 
 fn my_hash<T>(obj: T) -> u64
   where T: Hash
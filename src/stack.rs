@@ -14,15 +14,31 @@
 //! The API is still in development, one of these interfaces may
 //! change to mimic the `Vec` interface.
 
+use std::mem;
 use std::fmt::Debug;
 use std::hash::Hash;
 
 use adapton::engine::*;
+use art::{Art,cell,put,force};
 
 /// Common linked-list
 #[derive(Debug,PartialEq,Eq,Clone,Hash)]
 pub struct Stack<T:'static+Debug+Clone+Eq+Hash>(pub Option<Head<T>>);
 
+/// Summary of a list, from `Stack::stats`
+#[derive(Clone,Copy,PartialEq,Eq,Debug,Default)]
+pub struct StackStats {
+	/// number of elements
+	pub len: usize,
+	/// elements pushed with names
+	pub named: usize,
+	/// elements pushed without names
+	pub unnamed: usize,
+	/// rough size of the list nodes in bytes, not counting names, data
+	/// owned by the elements, or the incremental engine's own tables
+	pub bytes: usize,
+}
+
 /// Linked list with at least one element
 #[derive(Debug,PartialEq,Eq,Clone,Hash)]
 pub struct Head<T:'static+Debug+Clone+Eq+Hash> {
@@ -75,6 +91,20 @@ Stack<T> {
 		Iter{ next: self.0.clone() }
 	}
 
+	/// a summary of the list, in O(n)
+	pub fn stats(&self) -> StackStats {
+		let mut stats = StackStats::default();
+		let mut iter = self.iter();
+		loop {
+			let named = iter.name().is_some();
+			if iter.next().is_none() { break }
+			stats.len += 1;
+			if named { stats.named += 1 } else { stats.unnamed += 1 }
+		}
+		stats.bytes = stats.len * mem::size_of::<Body<T>>();
+		stats
+	}
+
 }
 
 impl<T:'static+Debug+Clone+Eq+Hash>
@@ -187,4 +217,16 @@ mod tests {
 		assert_eq!(vec![1,2,3,4], a.iter().collect::<Vec<_>>());
 	} 
 
+	#[test]
+	fn test_stats() {
+		let a = Stack::new()
+			.push(Some(name_of_usize(1)),1)
+			.push(None,2)
+			.push(Some(name_of_usize(3)),3);
+		let stats = a.stats();
+		assert_eq!((3,2,1), (stats.len, stats.named, stats.unnamed));
+		assert!(stats.bytes >= 3 * ::std::mem::size_of::<usize>());
+		assert_eq!(StackStats::default(), Stack::<usize>::new().stats());
+	}
+
 }
\ No newline at end of file
@@ -12,8 +12,11 @@ use std::fmt::Debug;
 use std::hash::Hash;
 use std::rc::Rc;
 use std::mem;
+use std::collections::HashSet;
 pub use level_tree::{Tree, gen_branch_level as gen_level};
+use level_tree::{Invalid, check_level};
 
+#[cfg(not(feature = "naive"))]
 use adapton::macros::*;
 use adapton::engine::*;
 
@@ -405,6 +408,59 @@ impl<'a,E: TreeUpdate+Debug+Clone+Eq+Hash+'static> Cursor<E> {
 		return if to_left { UpResult::Left } else { UpResult::Right };
 	}
 
+	/// check the structure of the focused tree and the forests
+	///
+	/// Walks the forests in the order `up()` would, checking levels
+	/// and name uniqueness over the whole underlying tree. Nodes that
+	/// are not marked dirty must still be the branch recorded in their
+	/// upper node. Returns every problem found. This is an O(n)
+	/// operation meant for tests and debugging.
+	pub fn validate(&self) -> Vec<Invalid> {
+		let mut errors = Vec::new();
+		let mut names = HashSet::new();
+		if let Some(ref t) = self.tree { t.validate_into(&mut names, &mut errors) }
+		let mut current = self.tree.clone();
+		let mut dirty = self.dirty;
+		let (mut l, mut r) = (self.l_forest.len(), self.r_forest.len());
+		loop {
+			let to_left = match (l, r) {
+				(0, 0) => break,
+				(_, 0) => true,
+				(0, _) => false,
+				(l, r) => self.r_forest[r-1].1.level() > self.l_forest[l-1].1.level(),
+			};
+			let &(upper_dirty, ref upper) = if to_left {
+				l -= 1; &self.l_forest[l]
+			} else {
+				r -= 1; &self.r_forest[r]
+			};
+			if let Some(n) = upper.name() {
+				if !names.insert(n.clone()) { errors.push(Invalid::DuplicateName(n)) }
+			}
+			// the branch we came from, and the one still to check
+			let (branch, other) = if to_left {
+				(upper.r_tree(), upper.l_tree())
+			} else {
+				(upper.l_tree(), upper.r_tree())
+			};
+			if let Some(ref t) = other {
+				errors.extend(check_level(upper.level(), t.level(), to_left));
+				t.validate_into(&mut names, &mut errors);
+			}
+			if let Some(ref t) = current {
+				errors.extend(check_level(upper.level(), t.level(), !to_left));
+			}
+			if !dirty {
+				if branch != current {
+					errors.push(Invalid::Dirty{level: upper.level(), name: upper.name()});
+				}
+				dirty = upper_dirty;
+			}
+			current = Some(upper.clone());
+		}
+		errors
+	}
+
 }
 
 #[derive(Debug,Clone,PartialEq,Eq,Hash)]
@@ -553,6 +609,52 @@ mod tests {
 		assert_eq!(Some(3), j.peek());
 	}
 
+	#[test]
+	fn test_validate() {
+		let t = 
+		Tree::new(5, Some(name_of_usize(5)),1,
+			Tree::new(3, Some(name_of_usize(3)),2,
+				Tree::new(0,None,4,None,None),
+				Tree::new(2, Some(name_of_usize(2)),5,
+					Tree::new(1, Some(name_of_usize(1)),8,
+						Tree::new(0,None,10,None,None),
+						Tree::new(0,None,11,None,None),
+					),
+					Tree::new(0,None,9,None,None),
+				)
+			),
+			Tree::new(4, Some(name_of_usize(4)),3,
+				Tree::new(0,None,6,None,None),
+				Tree::new(0,None,7,None,None),
+			)
+		).unwrap();
+		let mut c: Cursor<usize> = t.clone().into();
+		assert!(c.validate().is_empty());
+
+		assert!(c.down_left());
+		assert!(c.down_right());
+		assert!(c.down_left());
+		assert!(c.validate().is_empty());
+		assert!(c.down_left_force(Force::Discard));
+		assert!(c.validate().is_empty());
+		let (lc, _, rc) = c.clone().split();
+		assert!(lc.validate().is_empty());
+		assert!(rc.validate().is_empty());
+
+		// a clean focus that isn't the recorded branch
+		let mut bad = c.clone();
+		bad.dirty = false;
+		assert_eq!(vec![Invalid::Dirty{level: 2, name: Some(name_of_usize(2))}], bad.validate());
+
+		// an upper node that reuses a name from below
+		let mut bad: Cursor<usize> = t.into();
+		assert!(bad.down_left());
+		bad.r_forest.push((false, Tree::new(6, Some(name_of_usize(1)),0,None,None).unwrap()));
+		let errors = bad.validate();
+		assert!(errors.contains(&Invalid::DuplicateName(name_of_usize(1))));
+		assert!(errors.contains(&Invalid::Dirty{level: 6, name: Some(name_of_usize(1))}));
+	}
+
 	#[test]
 	fn test_iter_r() {
 		let t = 
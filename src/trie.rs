@@ -2,15 +2,30 @@
 //!
 //! Suitable for the Archivist role in Adapton.
 //!
+//! Keys are placed by the bits of their hash. Small maps are kept
+//! in leaves, which split into branches once they hold more than
+//! `LEAF_MAX` entries, so the shape of a trie depends only on its
+//! keys. Unions of tries given names are memoized on each branch,
+//! so a union after a small change reuses the unchanged branches.
+//!
 // Matthew Hammer <Matthew.Hammer@Colorado.edu>
 
-//use std::rc::Rc;
+use std::rc::Rc;
 use std::fmt;
 use std::fmt::Debug;
 use std::hash::{Hash,Hasher};
 use std::collections::HashMap;
 use std::collections::hash_map::DefaultHasher;
-use adapton::engine::{cell,name_fork,force,Art,Name};
+#[cfg(not(feature = "naive"))]
+use adapton::macros::*;
+use adapton::engine::*;
+use art::{cell,put,force,Art};
+
+/// Largest number of entries kept in a single leaf
+const LEAF_MAX: usize = 32;
+
+/// Hash bits available for placing keys
+const HASH_BITS: u32 = 64;
 
 fn my_hash<T>(obj: T) -> u64
   where T: Hash
@@ -30,20 +45,17 @@ impl fmt::Debug for HashVal {
     }
 }
 
-/// TODO-Someday: Do this more efficiently
-fn make_mask(len:usize) -> usize {
-    assert!(len > 0);
-    let mut mask = 0;
-    for _ in 0..len {
-        mask <<= 1;
-        mask |= 0x1;
+/// The hash prefix shared by all keys below a branch
+#[derive(PartialEq,Eq,Clone,Debug,Hash)]
+struct Bits {bits:u64, len:u32}
+
+impl Bits {
+    fn children(&self) -> (Bits, Bits) {
+        (Bits{len:self.len+1, bits:self.bits},
+         Bits{len:self.len+1, bits:self.bits | (1 << self.len)})
     }
-    return mask;
 }
 
-#[derive(PartialEq,Eq,Clone,Debug,Hash)]
-struct Bits {bits:u32, len:u32}
-
 #[derive(PartialEq,Eq,Clone,Debug, Hash)]
 pub enum Trie<K:'static+Hash+Eq+Clone+Debug,
               V:'static+Hash+Eq+Clone+Debug>
@@ -58,18 +70,23 @@ pub struct TrieLeaf<K:'static+Hash+Eq+Clone+Debug,
     hash:HashVal,
     map:HashMap<K,V>,
 }
-#[derive(Hash,PartialEq,Eq,Clone,Debug)]
+#[derive(Eq,Clone,Debug)]
 pub struct TrieBin<K:'static+Hash+Eq+Clone+Debug,
                    V:'static+Hash+Eq+Clone+Debug> {
     bits:   Bits,
-    name:   Name,
+    count:  usize,
+    hash:   HashVal,
+    name:   Option<Name>,
     left:   Art<Trie<K,V>>,
     right:  Art<Trie<K,V>>,
 }
 
+/// A finite set, as a trie with no values
+pub type FinSet<E> = Trie<E,()>;
+
 impl<K:'static+Hash+Eq+Clone+Debug,
      V:'static+Hash+Eq+Clone+Debug>
-    Hash for TrieLeaf<K,V> 
+    Hash for TrieLeaf<K,V>
 {
     fn hash<H:Hasher>(&self, h:&mut H) {
         self.hash.hash(h)
@@ -77,10 +94,34 @@ impl<K:'static+Hash+Eq+Clone+Debug,
 }
 impl<K:'static+Hash+Eq+Clone+Debug,
      V:'static+Hash+Eq+Clone+Debug>
-    PartialEq for TrieLeaf<K,V> 
+    PartialEq for TrieLeaf<K,V>
+{
+    fn eq(&self, other:&Self) -> bool {
+        self.hash == other.hash && self.map == other.map
+    }
+}
+
+// The cached hash covers the contents, so the children are not
+// hashed, and are only compared when the hashes agree
+impl<K:'static+Hash+Eq+Clone+Debug,
+     V:'static+Hash+Eq+Clone+Debug>
+    Hash for TrieBin<K,V>
+{
+    fn hash<H:Hasher>(&self, h:&mut H) {
+        self.bits.hash(h);
+        self.count.hash(h);
+        self.hash.hash(h);
+        self.name.hash(h)
+    }
+}
+impl<K:'static+Hash+Eq+Clone+Debug,
+     V:'static+Hash+Eq+Clone+Debug>
+    PartialEq for TrieBin<K,V>
 {
     fn eq(&self, other:&Self) -> bool {
-        self.hash == other.hash
+        self.hash == other.hash && self.count == other.count &&
+        self.bits == other.bits && self.name == other.name &&
+        self.left == other.left && self.right == other.right
     }
 }
 
@@ -88,38 +129,80 @@ impl<K:'static+Hash+Eq+Clone+Debug,
      V:'static+Hash+Eq+Clone+Debug> Trie<K,V> {
 
     pub fn find (t: &Self, h:HashVal, k:&K) -> Option<V> {
-        match t {
-            &Trie::Empty => None,
-            &Trie::Leaf(ref l) => match l.map.get(k) { Some(v) => Some(v.clone()), None => None },
-            &Trie::Bin(ref b) => {
+        match *t {
+            Trie::Empty => None,
+            Trie::Leaf(ref l) => l.map.get(k).cloned(),
+            Trie::Bin(ref b) => {
                 if h.0 & 1 == 0 {
-                    Self::find(&get!(b.left), HashVal(h.0 >> 1), k)
-                } else { 
-                    Self::find(&get!(b.right), HashVal(h.0 >> 1), k)
+                    Self::find(&force(&b.left), HashVal(h.0 >> 1), k)
+                } else {
+                    Self::find(&force(&b.right), HashVal(h.0 >> 1), k)
                 }
             }
         }
     }
 
+    /// the value stored with a key, if the key is present
+    pub fn get (&self, k:&K) -> Option<V> {
+        Self::find(self, HashVal(my_hash(k) as usize), k)
+    }
+
+    /// whether the key is present
+    pub fn contains (&self, k:&K) -> bool {
+        self.get(k).is_some()
+    }
+
+    /// the number of keys in the trie
+    pub fn len (&self) -> usize {
+        match *self {
+            Trie::Empty => 0,
+            Trie::Leaf(ref l) => l.map.len(),
+            Trie::Bin(ref b) => b.count,
+        }
+    }
+
+    pub fn is_empty (&self) -> bool { self.len() == 0 }
+
+    /// a hash of the keys and values, independent of the trie's shape
+    ///
+    /// Kept with each leaf and branch, so this takes constant time.
+    pub fn hash_val (&self) -> HashVal {
+        match *self {
+            Trie::Empty => HashVal(0),
+            Trie::Leaf(ref l) => l.hash.clone(),
+            Trie::Bin(ref b) => b.hash.clone(),
+        }
+    }
+
+    /// all the keys and values, in no particular order
+    pub fn to_vec (&self) -> Vec<(K,V)> {
+        let mut out = Vec::with_capacity(self.len());
+        self.collect(&mut out);
+        out
+    }
+
+    fn collect (&self, out: &mut Vec<(K,V)>) {
+        match *self {
+            Trie::Empty => {},
+            Trie::Leaf(ref l) => out.extend(l.map.iter().map(|(k,v)|(k.clone(),v.clone()))),
+            Trie::Bin(ref b) => {
+                force(&b.left).collect(out);
+                force(&b.right).collect(out);
+            }
+        }
+    }
+
+    // independent of the order of the map's iteration
     fn hash_map (map: &HashMap<K,V>) -> HashVal {
-        let mut state = DefaultHasher::new();
-        for (k,v) in map.iter() {
-            k.hash(&mut state);
-            v.hash(&mut state);
-        };
-        HashVal(state.finish() as usize)
+        let hash = map.iter().fold(0u64, |h, kv| h.wrapping_add(my_hash(kv)));
+        HashVal(hash as usize)
     }
 
-    fn split_map (map: HashMap<K,V>, bits:&Bits,
-                  mut map0:HashMap<K,V>, 
-                  mut map1:HashMap<K,V>)
-                  -> (HashMap<K,V>, HashMap<K,V>) 
-    {
-        let mask : u64 = make_mask(bits.len as usize) as u64;
-        for (k,v) in map.into_iter() {
-            let k_hash = my_hash(&k);
-            assert!(mask & k_hash == bits.bits as u64);
-            if 0 == k_hash & (1 << (bits.len + 1)) {
+    fn split_map (map: HashMap<K,V>, bits:&Bits) -> (HashMap<K,V>, HashMap<K,V>) {
+        let mut map0 = HashMap::new();
+        let mut map1 = HashMap::new();
+        for (k,v) in map {
+            if 0 == my_hash(&k) & (1 << bits.len) {
                 map0.insert(k, v);
             } else {
                 map1.insert(k, v);
@@ -128,74 +211,154 @@ impl<K:'static+Hash+Eq+Clone+Debug,
         (map0, map1)
     }
 
-    pub fn empty () -> Self { 
-        Trie::Empty 
+    pub fn empty () -> Self {
+        Trie::Empty
     }
 
-    pub fn from_hashmap(hm:HashMap<K,V>) -> Self { 
-        Trie::Leaf(TrieLeaf{hash:Self::hash_map(&hm), map:hm})
+    pub fn from_hashmap(hm:HashMap<K,V>) -> Self {
+        Self::from_map(hm, Bits{len:0, bits:0})
     }
 
-    pub fn union (lt: Self, rt: Self, n:Name) -> Self {
-        Self::union_rec(lt, rt, Bits{len:0, bits:0}, n)
+    fn from_map (map:HashMap<K,V>, bits:Bits) -> Self {
+        if map.is_empty() {
+            Trie::Empty
+        } else if map.len() <= LEAF_MAX || bits.len >= HASH_BITS {
+            Trie::Leaf(TrieLeaf{hash:Self::hash_map(&map), map})
+        } else {
+            let count = map.len();
+            let hash = Self::hash_map(&map);
+            let (map0, map1) = Self::split_map(map, &bits);
+            let (bits0, bits1) = bits.children();
+            Trie::Bin(TrieBin{
+                left: put(Self::from_map(map0, bits0)),
+                right: put(Self::from_map(map1, bits1)),
+                bits, count, hash, name: None,
+            })
+        }
     }
 
-    // Questions:
-    //
-    // 1. When do we decide to "merge" leaves?  Based on where we have
-    // or don't have names/levels?
-    //
-    // 2. Is there a way to shorten the code below? There seems to be
-    // patterns that I'm not exploiting.
+    // the two children of a trie, splitting leaves as needed
+    fn halves (t: Self, bits:&Bits) -> (Self, Self) {
+        match t {
+            Trie::Empty => (Trie::Empty, Trie::Empty),
+            Trie::Leaf(l) => {
+                let (map0, map1) = Self::split_map(l.map, bits);
+                let (bits0, bits1) = bits.children();
+                (Self::from_map(map0, bits0), Self::from_map(map1, bits1))
+            },
+            Trie::Bin(b) => (force(&b.left), force(&b.right)),
+        }
+    }
 
-    fn union_rec (lt: Self, rt: Self, bits:Bits, n:Name) -> Self {
+    /// all the keys of both tries
+    ///
+    /// Keys found in both keep the value from `lt`. With a name,
+    /// the union of each pair of branches is memoized under names
+    /// forked from it.
+    pub fn union (lt: Self, rt: Self, n:Option<Name>) -> Self {
+        Self::union_with(lt, rt, n, Rc::new(|_k:&K, l:V, _r:V| l))
+    }
+
+    /// all the keys of both tries, combining the values of keys
+    /// found in both
+    ///
+    /// `merge` is given the key, the value from `lt`, then the value
+    /// from `rt`. Memoized like `union`.
+    pub fn union_with<F> (lt: Self, rt: Self, n:Option<Name>, merge:Rc<F>) -> Self where
+        F: 'static + Fn(&K,V,V) -> V
+    {
+        Self::union_rec(lt, rt, Bits{len:0, bits:0}, n, merge)
+    }
+
+    fn union_rec<F> (lt: Self, rt: Self, bits:Bits, n:Option<Name>, merge:Rc<F>) -> Self where
+        F: 'static + Fn(&K,V,V) -> V
+    {
         match (lt, rt) {
-            (Trie::Empty, rt) => rt,
-            (lt, Trie::Empty) => lt,
+            (Trie::Empty, t) | (t, Trie::Empty) => t,
             (Trie::Leaf(l), Trie::Leaf(r)) => {
-                let (e0, e1) = (HashMap::new(), HashMap::new());
-                let (l0, l1) = Self::split_map(l.map, &bits, e0, e1);
-                let (r0, r1) = Self::split_map(r.map, &bits, l0, l1);
-                let (n1, n2) = name_fork(n.clone());
-                let lf1 = TrieLeaf{hash:Self::hash_map(&r0), map:r0};
-                let lf2 = TrieLeaf{hash:Self::hash_map(&r1), map:r1};
-                let r0 = cell(n1, Trie::Leaf(lf1));
-                let r1 = cell(n2, Trie::Leaf(lf2));
-                Trie::Bin(TrieBin{left:r0, right:r1, bits:bits, name:n})
-            },
-            (Trie::Leaf(_l), Trie::Bin(_r)) => {
-                panic!("TODO")
-                /*
-                assert!(r.bits == bits);
-                let lbits = Bits{len:bits.len+1, bits: bits.bits };
-                let rbits = Bits{len:bits.len+1, bits:(1 << bits.len) & bits.bits };
-                let (e0, e1) = (HashMap::new(), HashMap::new());
-                let (l0, l1) = Self::split_map(l.map, &bits, e0, e1);
-                let lf0 = TrieLeaf{hash:Self::hash_map(&l0), map:l0};
-                let lf1 = TrieLeaf{hash:Self::hash_map(&l1), map:l1};
-                let (n1, n2) = name_fork(n.clone());
-
-                let l = cell(n1, Self::union_rec(TrieLeaf{, get!(l.right), lbits, l.name));
-                let r = cell(n2, Self::union_rec(get!(r.left), get!(r.right), rbits, r.name));
-                */
-            },
-            (Trie::Bin(_l), Trie::Leaf(r)) => {
-                let (e0, e1) = (HashMap::new(), HashMap::new());
-                let (r0, r1) = Self::split_map(r.map, &bits, e0, e1);
-                
-                drop((r0, r1));
-                panic!("")
+                let mut map = r.map;
+                for (k,v) in l.map {
+                    let v = match map.remove(&k) {
+                        None => v,
+                        Some(rv) => merge(&k, v, rv),
+                    };
+                    map.insert(k, v);
+                }
+                Self::from_map(map, bits)
             },
-            (Trie::Bin(l), Trie::Bin(r)) => {
-                assert!(l.bits == bits);
-                assert!(l.bits == r.bits);
-                let lbits = Bits{len:bits.len+1, bits: bits.bits };
-                let rbits = Bits{len:bits.len+1, bits:(1 << bits.len) & bits.bits };
-                let (n1, n2) = name_fork(n.clone());
-                let l = cell(n1, Self::union_rec(get!(l.left), get!(l.right), lbits, l.name));
-                let r = cell(n2, Self::union_rec(get!(r.left), get!(r.right), rbits, r.name));
-                Trie::Bin(TrieBin{ left:l, right:r, name:n, bits:bits })
+            (lt, rt) => {
+                let (l0, l1) = Self::halves(lt, &bits);
+                let (r0, r1) = Self::halves(rt, &bits);
+                let (bits0, bits1) = bits.children();
+                let (t0, t1, left, right) = match n {
+                    None => {
+                        let t0 = Self::union_rec(l0, r0, bits0, None, merge.clone());
+                        let t1 = Self::union_rec(l1, r1, bits1, None, merge);
+                        (t0.clone(), t1.clone(), put(t0), put(t1))
+                    },
+                    Some(ref n) => {
+                        let (n0, n1) = name_fork(n.clone());
+                        let (m0, c0) = name_fork(n0);
+                        let (m1, c1) = name_fork(n1);
+                        let t0 = memo!( m0.clone() =>> Self::union_rec, l:l0, r:r0, b:bits0, n:Some(m0) ;; f:merge.clone() );
+                        let t1 = memo!( m1.clone() =>> Self::union_rec, l:l1, r:r1, b:bits1, n:Some(m1) ;; f:merge );
+                        (t0.clone(), t1.clone(), cell(c0, t0), cell(c1, t1))
+                    },
+                };
+                Trie::Bin(TrieBin{
+                    count: t0.len() + t1.len(),
+                    hash: HashVal(t0.hash_val().0.wrapping_add(t1.hash_val().0)),
+                    left, right, bits, name: n,
+                })
             }
         }
-    }               
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn set_of(range: ::std::ops::Range<usize>) -> FinSet<usize> {
+        Trie::from_hashmap(range.map(|i|(i,())).collect())
+    }
+
+    #[test]
+    fn test_union() {
+        let a = set_of(0..100);
+        let b = set_of(50..300);
+        assert_eq!(100, a.len());
+        let u = Trie::union(a.clone(), b.clone(), Some(name_of_string(String::from("union"))));
+        assert_eq!(300, u.len());
+        assert!(u.contains(&0) && u.contains(&150) && u.contains(&299));
+        assert!(!u.contains(&300));
+        let mut elms = u.to_vec().into_iter().map(|(e,_)|e).collect::<Vec<_>>();
+        elms.sort();
+        assert_eq!((0..300).collect::<Vec<_>>(), elms);
+
+        // same shape as a set built all at once
+        assert_eq!(set_of(0..300), Trie::union(a, b, None));
+        assert_eq!(set_of(0..300).hash_val(), u.hash_val());
+        assert!(set_of(0..299).hash_val() != u.hash_val());
+        assert_eq!(Trie::union(set_of(0..10), set_of(10..20), None), set_of(0..20));
+        assert!(Trie::union(FinSet::<usize>::empty(), Trie::empty(), None).is_empty());
+    }
+
+    #[test]
+    fn test_union_values() {
+        let a: Trie<usize,usize> = Trie::from_hashmap((0..50).map(|i|(i,1)).collect());
+        let b: Trie<usize,usize> = Trie::from_hashmap((0..100).map(|i|(i,2)).collect());
+        let u = Trie::union(a, b, None);
+        assert_eq!(Some(1), u.get(&10));
+        assert_eq!(Some(2), u.get(&70));
+        assert_eq!(None, u.get(&100));
+
+        let a: Trie<usize,usize> = Trie::from_hashmap((0..50).map(|i|(i,1)).collect());
+        let b: Trie<usize,usize> = Trie::from_hashmap((25..100).map(|i|(i,2)).collect());
+        let u = Trie::union_with(a, b, Some(name_of_usize(1)), Rc::new(|_:&usize,l,r|l+r));
+        assert_eq!(100, u.len());
+        assert_eq!(Some(1), u.get(&10));
+        assert_eq!(Some(3), u.get(&30));
+        assert_eq!(Some(2), u.get(&70));
+    }
 }
@@ -1,5 +1,6 @@
 cargo build --verbose
 cargo test --verbose -- --nocapture
+cargo test --verbose --features naive
 cd eval
 cargo run --release --example adder
 cargo run --release --example filter